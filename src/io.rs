@@ -0,0 +1,431 @@
+//! Loading and saving the files the viewer works on.
+
+use crate::model::{AuditRecord, Code, Entry, Report, Session};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+pub type Error = Box<dyn std::error::Error>;
+
+/// A file that could not be loaded on startup, and why.
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub error: Error,
+}
+
+/// Loads the entries from a JSON array, or from JSON Lines with one entry
+/// per line if `jsonl` is set or the file ends in `.jsonl`. Fails if two
+/// entries have the same index.
+pub fn load_entries(path: &Path, jsonl: bool) -> Result<Vec<Entry>, Error> {
+    let entries = read_entries(path, jsonl)?;
+
+    // Entries are told apart by their index, e.g. to resume or compare them
+    let duplicates = duplicate_indices(&entries);
+    if !duplicates.is_empty() {
+        let duplicates: Vec<String> = duplicates.iter().map(u32::to_string).collect();
+        return Err(format!(
+            "These indices are used by more than one entry: {}",
+            duplicates.join(", ")
+        )
+        .into());
+    }
+    Ok(entries)
+}
+
+fn read_entries(path: &Path, jsonl: bool) -> Result<Vec<Entry>, Error> {
+    let file = File::open(path)?;
+    if !jsonl && path.extension().is_none_or(|ext| ext != "jsonl") {
+        return migrate(serde_json::from_reader(file)?);
+    }
+
+    let mut entries = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| format!("Line {}: {}", n + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Loads and merges the codes of every file in `paths`. A tag that is in
+/// more than one file is kept from the first, with a warning if the others
+/// describe it differently.
+pub fn load_all_codes(paths: &[PathBuf]) -> Result<Vec<Code>, LoadError> {
+    let mut codes: Vec<Code> = Vec::new();
+    for path in paths {
+        let loaded = load_codes(path).map_err(|error| LoadError {
+            path: path.clone(),
+            error,
+        })?;
+
+        // With duplicate tags it's unclear which code a toggle is for
+        let duplicates = duplicate_tags(&loaded);
+        if !duplicates.is_empty() {
+            return Err(LoadError {
+                path: path.clone(),
+                error: format!(
+                    "These tags are used by more than one code: {}",
+                    duplicates.join(", ")
+                )
+                .into(),
+            });
+        }
+
+        let earlier = codes.len();
+        for code in loaded {
+            match codes[..earlier].iter().find(|c| c.tag == code.tag) {
+                Some(known) if known.code != code.code => eprintln!(
+                    "Warning: {} describes {} as \"{}\", keeping \"{}\"",
+                    path.display(),
+                    code.tag,
+                    code.code,
+                    known.code
+                ),
+                Some(_) => (),
+                None => codes.push(code),
+            }
+        }
+    }
+    Ok(codes)
+}
+
+/// The tags that more than one code in the codebook has.
+fn duplicate_tags(codes: &[Code]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<String> = codes
+        .iter()
+        .filter(|c| !seen.insert(c.tag.as_str()))
+        .map(|c| c.tag.clone())
+        .collect();
+    duplicates.sort();
+    duplicates.dedup();
+    duplicates
+}
+
+/// The indices that more than one entry has.
+fn duplicate_indices(entries: &[Entry]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<u32> = entries
+        .iter()
+        .filter(|e| !seen.insert(e.index))
+        .map(|e| e.index)
+        .collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    duplicates
+}
+
+/// Where the keymap is read from if there is no --keymap.
+pub fn default_keymap_path() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config.join("openresponseviewer").join("keys.toml"))
+}
+
+/// Loads a keymap of key labels to the names of the messages they send.
+pub fn load_keymap(path: &Path) -> Result<HashMap<String, String>, Error> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(toml::from_str(&text)?)
+}
+
+pub fn load_session(path: &Path) -> Result<Session, Error> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+/// The version of the format `entries_json` writes. Version 0 is a plain
+/// array of entries, from before the format had a version.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// The entries along with the version of the format they are in.
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u64,
+    entries: &'a [&'a Entry],
+}
+
+/// Reads entries saved in any version of the format, upgrading them to the
+/// current one.
+fn migrate(value: serde_json::Value) -> Result<Vec<Entry>, Error> {
+    let (version, entries) = match value {
+        serde_json::Value::Array(_) => (0, value),
+        serde_json::Value::Object(mut object) => {
+            let version = object
+                .get("version")
+                .and_then(|v| v.as_u64())
+                .ok_or("The file has no version")?;
+            let entries = object.remove("entries").ok_or("The file has no entries")?;
+            (version, entries)
+        }
+        _ => return Err("Expected a list of entries".into()),
+    };
+
+    match version {
+        // The fields added since version 0 all have defaults
+        0 | FORMAT_VERSION => Ok(serde_json::from_value(entries)?),
+        _ => Err(format!(
+            "The file is in version {} of the format, but only up to {} is supported",
+            version, FORMAT_VERSION
+        )
+        .into()),
+    }
+}
+
+/// The contents of an output file of `data`.
+pub fn entries_json(data: &[&Entry]) -> Result<Vec<u8>, Error> {
+    let versioned = Versioned {
+        version: FORMAT_VERSION,
+        entries: data,
+    };
+    Ok(serde_json::to_vec_pretty(&versioned)?)
+}
+
+pub fn session_json(session: &Session) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec(session)?)
+}
+
+/// Adds `records` to the end of the audit log at `path`, one per line.
+pub fn append_audit(path: &Path, records: &[AuditRecord]) -> Result<(), Error> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut lines = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut lines, record)?;
+        lines.push(b'\n');
+    }
+    // In one write, so that a line is never split by a crash
+    file.write_all(&lines)?;
+    Ok(file.sync_data()?)
+}
+
+/// Writes `contents` to `path` atomically, for contents that were made
+/// ahead of time, e.g. to be written in the background.
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    write_atomically(path, |file| Ok(file.write_all(contents)?))
+}
+
+pub fn export_report(path: &Path, report: &Report) -> Result<(), Error> {
+    write_atomically(path, |file| Ok(serde_json::to_writer_pretty(file, report)?))
+}
+
+/// Writes `entries` as a plain array, which loads as version 0 of the format.
+pub fn export_entries(path: &Path, entries: &[&Entry]) -> Result<(), Error> {
+    write_atomically(path, |file| {
+        Ok(serde_json::to_writer_pretty(file, entries)?)
+    })
+}
+
+/// Writes `data` as a flat CSV, with one true/false column per code.
+/// `responses` has the response of each entry, which may be from its file.
+pub fn export_csv(
+    path: &Path,
+    codes: &[Code],
+    data: &[Entry],
+    responses: &[&str],
+) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut writer = csv::Writer::from_path(path)?;
+
+    let mut header = vec!["index", "lab", "group", "response", "matches"];
+    header.extend(codes.iter().map(|c| c.tag.as_str()));
+    writer.write_record(&header)?;
+
+    for (entry, response) in data.iter().zip(responses) {
+        let mut record = vec![
+            entry.index.to_string(),
+            entry.lab.clone(),
+            entry.group.clone(),
+            response.to_string(),
+            entry.matches.map(|b| b.to_string()).unwrap_or_default(),
+        ];
+        record.extend(
+            codes
+                .iter()
+                .map(|c| entry.codes.contains(&c.tag).to_string()),
+        );
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Appends `suffix` to the file name of `path`, e.g. `out.json` to
+/// `out.json.session`.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Writes `path` by writing a temporary file next to it first and renaming
+/// that over it, so that `path` is never left half-written.
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = with_suffix(path, ".tmp");
+    let written = File::create(&tmp_path)
+        .map_err(Error::from)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            Ok(file.sync_all()?)
+        });
+
+    match written {
+        Ok(()) => Ok(std::fs::rename(&tmp_path, path)?),
+        Err(err) => {
+            // The original is untouched, only the partial copy goes
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(err)
+        }
+    }
+}
+
+/// The columns every codes file needs.
+const CODE_COLUMNS: [&str; 3] = ["theme", "tag", "code"];
+
+/// Loads the codebook, skipping (and reporting) any rows that can't be read.
+/// Fails if the header is wrong, or if no row could be read at all.
+pub fn load_codes(path: &Path) -> Result<Vec<Code>, Error> {
+    let file = File::open(path)?;
+    let mut reader = csv::Reader::from_reader(file);
+
+    let headers = reader.headers()?.clone();
+    let missing: Vec<&str> = CODE_COLUMNS
+        .iter()
+        .filter(|&&column| !headers.iter().any(|h| h == column))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "The codes file needs the columns {}, but has no {} column",
+            CODE_COLUMNS.join(", "),
+            missing.join(", ")
+        )
+        .into());
+    }
+
+    let mut codes = Vec::new();
+    let mut dropped = 0;
+    for row in reader.deserialize() {
+        match row {
+            Ok(code) => codes.push(code),
+            Err(err) => {
+                eprintln!("Skipping a row of {}: {}", path.display(), err);
+                dropped += 1;
+            }
+        }
+    }
+
+    if dropped > 0 {
+        if codes.is_empty() {
+            return Err(format!(
+                "None of the {} rows in the codes file could be read",
+                dropped
+            )
+            .into());
+        }
+        eprintln!(
+            "Skipped {} of {} rows in {}",
+            dropped,
+            dropped + codes.len(),
+            path.display()
+        );
+    }
+    Ok(codes)
+}
+
+/// A row of a file of matches decisions, where an empty decision is none.
+#[derive(Deserialize)]
+struct MatchesRow {
+    index: u32,
+    matches: Option<bool>,
+}
+
+/// Loads the matches decisions made outside the viewer from a CSV file with
+/// the columns index and matches, skipping the rows without a decision.
+/// Fails on the first row that can't be read.
+pub fn load_matches(path: &Path) -> Result<Vec<(u32, bool)>, Error> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut decisions = Vec::new();
+    for (n, row) in reader.deserialize().enumerate() {
+        // The header is the first line
+        let row: MatchesRow = row.map_err(|e| format!("Line {}: {}", n + 2, e))?;
+        if let Some(matches) = row.matches {
+            decisions.push((row.index, matches));
+        }
+    }
+    Ok(decisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for a test, named after `name`.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "response_viewer_io_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_original() {
+        let path = test_dir("failed_write").join("out.json");
+        std::fs::write(&path, "original").unwrap();
+
+        let written = write_atomically(&path, |file| {
+            file.write_all(b"partial")?;
+            Err("failed".into())
+        });
+        assert!(written.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    fn code(tag: &str) -> Code {
+        Code {
+            theme: String::from("theme"),
+            tag: tag.to_string(),
+            code: tag.to_uppercase(),
+            exclusive: None,
+            hotkey: None,
+            theme_order: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn a_tag_used_twice_is_a_duplicate_once() {
+        let codes = [code("a"), code("b"), code("a"), code("c"), code("a")];
+        assert_eq!(duplicate_tags(&codes), vec![String::from("a")]);
+        assert!(duplicate_tags(&codes[1..4]).is_empty());
+    }
+
+    #[test]
+    fn writing_creates_the_missing_directories() {
+        let path = test_dir("nested").join("a").join("b").join("out.json");
+        write_file(&path, b"[]").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+    }
+}
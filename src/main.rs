@@ -1,271 +1,4480 @@
+mod io;
+mod markdown;
+mod model;
+
+use clap::{ErrorKind, IntoApp, Parser, ValueEnum};
 use iced::{
     button,
     container::{Style, StyleSheet},
-    executor, Align, Application, Button, Checkbox, Clipboard, Column, Command, Container, Element,
-    Settings, Subscription, Text,
+    executor, pick_list, scrollable, text_input, tooltip, Align, Application, Button, Checkbox,
+    Clipboard, Column, Command, Container, Element, PickList, ProgressBar, Radio, Scrollable,
+    Settings, Space, Subscription, Text, TextInput, Tooltip,
+};
+use iced_native::{keyboard, mouse, window, Event};
+use io::{load_all_codes, load_entries, load_keymap, load_session, with_suffix, Error, LoadError};
+use model::{AuditRecord, Code, CodeCount, Entry, GroupProgress, Report, Session};
+use std::{
+    borrow::Cow,
+    cmp::{min, Ordering},
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use iced_native::{keyboard, Event};
-use serde::{Deserialize, Serialize};
-use std::{cmp::min, collections::HashSet, fs::File, io::BufRead, path::Path};
 
-type Error = Box<dyn std::error::Error>;
+/// View and code free-text responses against a codebook.
+///
+/// The three paths may also be given positionally, in the order
+/// INPUT CODES OUTPUT.
+#[derive(Debug, Parser)]
+#[clap(version, about)]
+struct Args {
+    /// The JSON file with the responses to code
+    #[clap(long, value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Read the input as JSON Lines, which is also assumed for .jsonl files
+    #[clap(long)]
+    jsonl: bool,
+
+    /// The CSV file with the codebook (theme, tag, code). Can be given more
+    /// than once, or as a comma-separated list, to merge several codebooks
+    #[clap(
+        long,
+        value_name = "FILE",
+        multiple_occurrences = true,
+        use_value_delimiter = true
+    )]
+    codes: Vec<PathBuf>,
+
+    /// The JSON file the coded responses are written to
+    #[clap(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Only save when asked to with Ctrl+S, instead of after every change.
+    /// Closing the window with unsaved changes asks whether to save them
+    #[clap(long)]
+    no_autosave: bool,
+
+    /// Review the entries in a random order, which is the same for the same
+    /// seed, given as --shuffle=SEED. Without one, the seed that was used is
+    /// printed
+    #[clap(long, value_name = "SEED", require_equals = true)]
+    shuffle: Option<Option<u64>>,
+
+    /// Keep all edits in memory and save once when the window is closed,
+    /// or when asked to with Ctrl+S
+    #[clap(long)]
+    save_on_exit: bool,
+
+    /// Another coder's output file to compare the codes with, by index
+    #[clap(long, value_name = "FILE")]
+    compare: Option<PathBuf>,
+
+    /// A CSV file of matches decisions made elsewhere, with the columns
+    /// index and matches, to set on the entries that have none yet
+    #[clap(long, value_name = "CSV")]
+    import_matches: Option<PathBuf>,
+
+    /// Overwrite an output file that has entries which are not in the input,
+    /// without asking first
+    #[clap(long)]
+    force: bool,
+
+    /// Add every change to the matches and codes to <OUTPUT>.log.jsonl, with
+    /// the time it was made
+    #[clap(long)]
+    audit_log: bool,
+
+    /// Don't copy an existing output file to <OUTPUT>.bak on startup
+    #[clap(long)]
+    no_backup: bool,
+
+    /// A TOML file of keys to change, e.g. j = "NextRow", which is read from
+    /// ~/.config/openresponseviewer/keys.toml if it exists
+    #[clap(long, value_name = "FILE")]
+    keymap: Option<PathBuf>,
+
+    /// Go to the previous and next entry by scrolling the mouse wheel
+    /// anywhere but over the response
+    #[clap(long)]
+    wheel_nav: bool,
+
+    /// Browse without ever writing the output file
+    #[clap(long)]
+    read_only: bool,
+
+    /// After a matches decision, move on to the next unreviewed entry
+    #[clap(long)]
+    auto_advance: bool,
+
+    /// Only count an entry as reviewed once it has a code as well as a
+    /// matches decision
+    #[clap(long)]
+    require_code: bool,
+
+    /// Start in the list of entries instead of on a single entry. L switches
+    /// between the two
+    #[clap(long)]
+    list_mode: bool,
+
+    /// The width in pixels to wrap responses at, instead of the window width
+    #[clap(long, value_name = "PIXELS")]
+    wrap_width: Option<u32>,
+
+    /// The width in pixels to open the window at, instead of the width it
+    /// was left at
+    #[clap(long, value_name = "PIXELS")]
+    width: Option<u32>,
+
+    /// The height in pixels to open the window at, instead of the height it
+    /// was left at
+    #[clap(long, value_name = "PIXELS")]
+    height: Option<u32>,
+
+    /// Number the lines of the responses, to refer to them in the notes
+    #[clap(long)]
+    line_numbers: bool,
+
+    /// Show the headings, lists and emphasis of responses in Markdown
+    #[clap(long)]
+    markdown: bool,
+
+    /// The size of the text, which can also be changed with Ctrl+Plus and
+    /// Ctrl+Minus
+    #[clap(long, value_name = "SIZE", default_value_t = 18)]
+    text_size: u16,
+
+    /// Review the entries sorted by these fields, e.g. lab,group, instead
+    /// of in the order of the input
+    #[clap(long, value_name = "FIELDS", value_enum, use_value_delimiter = true)]
+    sort_by: Vec<SortKey>,
+
+    #[clap(value_name = "PATHS", max_values = 3, hide = true)]
+    paths: Vec<PathBuf>,
+}
+
+/// A field the entries can be sorted by.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SortKey {
+    Lab,
+    Group,
+    Index,
+}
+
+impl SortKey {
+    fn compare(self, a: &Entry, b: &Entry) -> Ordering {
+        match self {
+            SortKey::Lab => a.lab.cmp(&b.lab),
+            SortKey::Group => a.group.cmp(&b.group),
+            SortKey::Index => a.index.cmp(&b.index),
+        }
+    }
+}
+
+/// Puts `data` in the order it's reviewed in, sorted by `sort_by` and then
+/// shuffled if there is a seed. Returns where each entry was before it was
+/// shuffled, to save them in that order.
+fn arrange(data: &mut Vec<Entry>, sort_by: &[SortKey], seed: Option<u64>) -> Option<Vec<usize>> {
+    // The sort is stable, so entries that are equal keep the input order
+    data.sort_by(|a, b| {
+        sort_by.iter().fold(Ordering::Equal, |order, key| {
+            order.then_with(|| key.compare(a, b))
+        })
+    });
+
+    seed.map(|seed| {
+        let mut order: Vec<usize> = (0..data.len()).collect();
+        shuffle(&mut order, seed);
+        let mut entries: Vec<Option<Entry>> = data.drain(..).map(Some).collect();
+        *data = order.iter().map(|&i| entries[i].take().unwrap()).collect();
+        order
+    })
+}
+
+/// Takes over the coding of the `saved` entries onto the ones in `data`
+/// with the same index, whatever order either is in. Returns how many of
+/// `saved` are not in `data`, in which case none are taken over.
+fn resume_all(data: &mut [Entry], saved: Vec<Entry>) -> usize {
+    let positions: HashMap<u32, usize> =
+        data.iter().enumerate().map(|(i, e)| (e.index, i)).collect();
+    let others = saved
+        .iter()
+        .filter(|e| !positions.contains_key(&e.index))
+        .count();
+    if others == 0 {
+        for entry in saved {
+            data[positions[&entry.index]].resume(entry);
+        }
+    }
+    others
+}
+
+/// Puts `items` in a random order, which is always the same for the same
+/// `seed`. This is a Fisher-Yates shuffle driven by SplitMix64.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// A seed for `shuffle` that differs between runs.
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+impl Args {
+    /// Fills in any missing flags from the positional paths, and exits
+    /// with an error if one is still missing afterwards, or if there are
+    /// paths left over.
+    fn resolve(mut self) -> Self {
+        let mut paths = std::mem::take(&mut self.paths).into_iter();
+        if self.input.is_none() {
+            self.input = paths.next();
+        }
+        if self.codes.is_empty() {
+            self.codes.extend(paths.next());
+        }
+        if self.output.is_none() {
+            self.output = paths.next();
+        }
+        // A path that fills in nothing was most likely meant for a flag
+        let extra: Vec<String> = paths.map(|path| path.display().to_string()).collect();
+        if !extra.is_empty() {
+            Args::command()
+                .error(
+                    ErrorKind::UnknownArgument,
+                    format!("Unexpected paths: {}", extra.join(" ")),
+                )
+                .exit();
+        }
+
+        for (name, missing) in [
+            ("--input", self.input.is_none()),
+            ("--codes", self.codes.is_empty()),
+            ("--output", self.output.is_none()),
+        ] {
+            if missing {
+                Args::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        format!("Missing required path: {}", name),
+                    )
+                    .exit();
+            }
+        }
+
+        // Unless it's given, the window opens at the size it was left at
+        let session = self
+            .output
+            .as_ref()
+            .and_then(|output| load_session(&with_suffix(output, ".session")).ok());
+        if let Some((width, height)) = session.and_then(|session| session.window_size) {
+            self.width.get_or_insert(width);
+            self.height.get_or_insert(height);
+        }
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Message {
     NextRow,
     PrevRow,
-    Matches(bool),
+    FirstRow,
+    LastRow,
+    ShowSummary,
+    ExitSummary,
+    ShowStats,
+    ToggleList,
+    ScrollToTop,
+    TogglePaths,
+    ToggleCodes,
+    ToggleLongLines,
+    Reload,
+    HistoryBack,
+    HistoryForward,
+    // Leaves the list for the entry at this position
+    OpenEntry(usize),
+    ToggleHelp,
+    ToggleDebug,
+    Pin,
+    Unpin,
+    NextUnreviewed,
+    NextUncoded,
+    FilterByCode(String),
+    FilterMatches(Option<bool>),
+    CycleMatchesFilter,
+    ToggleNotesFilter,
+    ClearFilter,
+    NextGroup,
+    PrevGroup,
+    Search(String),
+    NextMatch,
+    PrevMatch,
+    JumpInput(String),
+    JumpTo(String),
+    // Leave the text inputs, so that keys are shortcuts again
+    Unfocus,
+    ConfirmOverwrite,
+    Matches(Option<bool>),
     ToggleMatches,
+    SetMatch(bool),
+    ClearMatch,
+    Advance,
     CodeToggle(String, bool),
+    CodeSelect(String, String),
+    // Removes the codes of a theme from the current entry
+    ClearTheme(String),
+    GroupCodePicked(String),
+    ApplyCodeToGroup(String),
+    RemoveCodeFromGroup(String),
+    RenameFrom(String),
+    RenameTo(String),
+    RenameCode(String, String),
+    CodeKey(usize),
+    ToggleThemeCollapsed(String),
+    CodeFilter(String),
+    NotesChanged(String),
+    ToggleFlag,
+    NextFlagged,
+    NextNoted,
+    NextDisagreement,
+    NextImportDisagreement,
+    NextUnknownCode,
+    RemoveUnknownCodes,
+    Save,
+    ExportCsv,
+    ExportFlagged,
+    ExportReport,
+    CopyResponse,
+    ToggleTheme,
+    TextSize(i16),
+    Undo,
+    Redo,
+    CloseRequested,
+    Tick,
+    // The answers to closing with unsaved changes
+    SaveAndClose,
+    DiscardAndClose,
+    CancelClose,
+    Resized(u32, u32),
+    // How a save in the background went
+    Saved(Result<(), String>),
+    // The time to save a change has come, if no other change came since
+    SaveDue(u64),
+    // A key press, which is looked up in the keymap and then the shortcuts
+    Key(keyboard::KeyCode, keyboard::Modifiers),
+    // A typed character, which is looked up in the hotkeys of the codes and
+    // then the shortcuts
+    Char(char),
+    // By how much the mouse wheel was scrolled up
+    Wheel(f32),
     Ignore,
 }
 
-struct AppStyle {}
-impl StyleSheet for AppStyle {
-    fn style(&self) -> Style {
-        Style {
-            text_color: Some(iced::Color::from_rgb8(0x83, 0x94, 0x96)),
-            background: Some(iced::Background::Color(iced::Color::from_rgb8(
-                0x00, 0x2B, 0x36,
-            ))),
-            border_radius: 0.0,
-            border_width: 0.0,
-            border_color: iced::Color::TRANSPARENT,
+impl Message {
+    /// Whether the message changes the data, rather than how it is viewed.
+    fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Message::Matches(_)
+                | Message::ToggleMatches
+                | Message::SetMatch(_)
+                | Message::ClearMatch
+                | Message::CodeToggle(..)
+                | Message::CodeSelect(..)
+                | Message::ClearTheme(_)
+                | Message::ApplyCodeToGroup(_)
+                | Message::RemoveCodeFromGroup(_)
+                | Message::RenameCode(..)
+                | Message::RemoveUnknownCodes
+                | Message::CodeKey(_)
+                | Message::NotesChanged(_)
+                | Message::ToggleFlag
+                | Message::Undo
+                | Message::Redo
+        )
+    }
+}
+
+const ERROR_COLOR: iced::Color = iced::Color {
+    r: 0xDC as f32 / 255.0,
+    g: 0x32 as f32 / 255.0,
+    b: 0x2F as f32 / 255.0,
+    a: 1.0,
+};
+
+const MUTED_COLOR: iced::Color = iced::Color {
+    r: 0x65 as f32 / 255.0,
+    g: 0x7B as f32 / 255.0,
+    b: 0x83 as f32 / 255.0,
+    a: 1.0,
+};
+
+/// The color scheme of the window, Solarized dark or light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Theme {
+    Dark,
+    Light,
+}
+
+const FLAG_COLOR: iced::Color = iced::Color {
+    r: 0xCB as f32 / 255.0,
+    g: 0x4B as f32 / 255.0,
+    b: 0x16 as f32 / 255.0,
+    a: 1.0,
+};
+
+struct AppStyle {
+    theme: Theme,
+}
+impl StyleSheet for AppStyle {
+    fn style(&self) -> Style {
+        let (text, background) = match self.theme {
+            Theme::Dark => (
+                iced::Color::from_rgb8(0x83, 0x94, 0x96),
+                iced::Color::from_rgb8(0x00, 0x2B, 0x36),
+            ),
+            Theme::Light => (
+                iced::Color::from_rgb8(0x65, 0x7B, 0x83),
+                iced::Color::from_rgb8(0xFD, 0xF6, 0xE3),
+            ),
+        };
+        Style {
+            text_color: Some(text),
+            background: Some(iced::Background::Color(background)),
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: iced::Color::TRANSPARENT,
+        }
+    }
+}
+
+/// The box the context of a response is shown in, set off from the window.
+struct ContextStyle {
+    theme: Theme,
+}
+impl StyleSheet for ContextStyle {
+    fn style(&self) -> Style {
+        let background = match self.theme {
+            Theme::Dark => iced::Color::from_rgb8(0x07, 0x36, 0x42),
+            Theme::Light => iced::Color::from_rgb8(0xEE, 0xE8, 0xD5),
+        };
+        Style {
+            text_color: None,
+            background: Some(iced::Background::Color(background)),
+            border_radius: 5.0,
+            border_width: 1.0,
+            border_color: MUTED_COLOR,
+        }
+    }
+}
+
+/// The look of the checkboxes and radio buttons in read-only mode, which
+/// do nothing.
+struct Dimmed {}
+impl iced::checkbox::StyleSheet for Dimmed {
+    fn active(&self, _is_checked: bool) -> iced::checkbox::Style {
+        iced::checkbox::Style {
+            background: iced::Background::Color(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+            checkmark_color: iced::Color::from_rgb(0.3, 0.3, 0.3),
+            border_radius: 5.0,
+            border_width: 1.0,
+            border_color: iced::Color::from_rgb(0.5, 0.5, 0.5),
+        }
+    }
+
+    fn hovered(&self, is_checked: bool) -> iced::checkbox::Style {
+        self.active(is_checked)
+    }
+}
+impl iced::radio::StyleSheet for Dimmed {
+    fn active(&self) -> iced::radio::Style {
+        iced::radio::Style {
+            background: iced::Background::Color(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+            dot_color: iced::Color::from_rgb(0.3, 0.3, 0.3),
+            border_width: 1.0,
+            border_color: iced::Color::from_rgb(0.5, 0.5, 0.5),
+        }
+    }
+
+    fn hovered(&self) -> iced::radio::Style {
+        self.active()
+    }
+}
+
+struct HighlightStyle {}
+impl StyleSheet for HighlightStyle {
+    fn style(&self) -> Style {
+        Style {
+            text_color: Some(iced::Color::from_rgb8(0x00, 0x2B, 0x36)),
+            background: Some(iced::Background::Color(iced::Color::from_rgb8(
+                0xB5, 0x89, 0x00,
+            ))),
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: iced::Color::TRANSPARENT,
+        }
+    }
+}
+
+/// A cell of the minimap, colored by the matches decision of its entry.
+struct MinimapCell {
+    matches: Option<bool>,
+    current: bool,
+}
+impl StyleSheet for MinimapCell {
+    fn style(&self) -> Style {
+        let color = match self.matches {
+            Some(true) => iced::Color::from_rgb8(0x85, 0x99, 0x00),
+            Some(false) => ERROR_COLOR,
+            None => iced::Color::from_rgb8(0x93, 0xA1, 0xA1),
+        };
+        Style {
+            text_color: None,
+            background: Some(iced::Background::Color(color)),
+            border_radius: 0.0,
+            border_width: if self.current { 2.0 } else { 0.0 },
+            border_color: iced::Color::from_rgb8(0x26, 0x8B, 0xD2),
+        }
+    }
+}
+
+/// The response of `entry`, from its file if that is in `loaded`.
+fn response_text<'a>(entry: &'a Entry, loaded: &'a HashMap<u32, Option<String>>) -> &'a str {
+    match loaded.get(&entry.index) {
+        Some(Some(text)) => text,
+        _ => &entry.response,
+    }
+}
+
+/// The number of characters a line is cut to, unless it's shown in full.
+/// Longer lines make the layout slow.
+const LONG_LINE: usize = 2000;
+
+/// The number of characters a word can have before it's broken up, as text
+/// only wraps between words.
+const LONG_WORD: usize = 80;
+
+/// `text` with the lines longer than `LONG_LINE` cut short, and whether any
+/// were.
+fn shortened(text: &str) -> (Cow<'_, str>, bool) {
+    if text
+        .split('\n')
+        .all(|line| line.chars().nth(LONG_LINE).is_none())
+    {
+        return (Cow::Borrowed(text), false);
+    }
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| match line.char_indices().nth(LONG_LINE) {
+            Some((end, _)) => format!("{}…", &line[..end]),
+            None => line.to_string(),
+        })
+        .collect();
+    (Cow::Owned(lines.join("\n")), true)
+}
+
+/// `text` with its words longer than `LONG_WORD` broken onto lines of their
+/// own, so that they can't be wider than the response.
+fn broken_words(text: &str) -> Cow<'_, str> {
+    let mut run = 0;
+    if text.chars().all(|c| {
+        run = if c.is_whitespace() { 0 } else { run + 1 };
+        run <= LONG_WORD
+    }) {
+        return Cow::Borrowed(text);
+    }
+    let mut broken = String::with_capacity(text.len());
+    let mut run = 0;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            run = 0;
+        } else if run == LONG_WORD {
+            broken.push('\n');
+            run = 1;
+        } else {
+            run += 1;
+        }
+        broken.push(c);
+    }
+    Cow::Owned(broken)
+}
+
+/// A bar under the cells of the minimap in the color of their group.
+struct GroupBar {
+    color: iced::Color,
+}
+impl StyleSheet for GroupBar {
+    fn style(&self) -> Style {
+        Style {
+            text_color: None,
+            background: Some(iced::Background::Color(self.color)),
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: iced::Color::TRANSPARENT,
+        }
+    }
+}
+
+/// A color for `group` that is always the same for the same name. It's of
+/// medium brightness, to show on both the dark and the light background.
+fn group_color(group: &str) -> iced::Color {
+    // FNV-1a, which unlike the hasher of the standard library is the same
+    // everywhere
+    let hash = group.bytes().fold(0xCBF2_9CE4_8422_2325_u64, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01B3)
+    });
+    let hue = (hash % 360) as f32 / 60.0;
+    let (saturation, value) = (0.6, 0.75);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    iced::Color::from_rgb(r + m, g + m, b + m)
+}
+
+/// A strip with a cell for every entry, showing which were reviewed and
+/// how, with the one at `idx` marked. Over the cells of the entries with
+/// notes is a small mark, and under the cells a bar shows which group each
+/// entry is in.
+fn minimap<'a>(data: &[Entry], idx: usize) -> Element<'a, Message> {
+    let mut strip = iced::Row::new()
+        .padding(10)
+        .width(iced::Length::Fill)
+        .align_items(Align::End);
+    for (i, entry) in data.iter().enumerate() {
+        let current = i == idx;
+        let note_mark = if entry.notes.is_empty() {
+            Container::new(Space::new(iced::Length::Fill, iced::Length::Fill))
+        } else {
+            Container::new(Space::new(iced::Length::Fill, iced::Length::Fill))
+                .style(GroupBar { color: MUTED_COLOR })
+        };
+        strip = strip.push(
+            Column::new()
+                .width(iced::Length::FillPortion(1))
+                .spacing(2)
+                .push(
+                    note_mark
+                        .width(iced::Length::Fill)
+                        .height(iced::Length::Units(3)),
+                )
+                .push(
+                    Container::new(Space::new(iced::Length::Fill, iced::Length::Fill))
+                        .width(iced::Length::Fill)
+                        .height(iced::Length::Units(if current { 14 } else { 10 }))
+                        .style(MinimapCell {
+                            matches: entry.matches,
+                            current,
+                        }),
+                )
+                .push(
+                    Container::new(Space::new(iced::Length::Fill, iced::Length::Fill))
+                        .width(iced::Length::Fill)
+                        .height(iced::Length::Units(4))
+                        .style(GroupBar {
+                            color: group_color(&entry.group),
+                        }),
+                ),
+        );
+    }
+    strip.into()
+}
+
+/// The byte ranges of the non-overlapping, case-insensitive occurrences of
+/// `query` in `text`.
+fn match_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let mut ranges = Vec::new();
+    if query.is_empty() {
+        return ranges;
+    }
+
+    let mut from = 0;
+    for (start, _) in text.char_indices() {
+        if start < from {
+            continue;
+        }
+
+        let mut want = query.iter();
+        let mut end = None;
+        'chars: for (i, c) in text[start..].char_indices() {
+            for l in c.to_lowercase() {
+                match want.next() {
+                    Some(&w) if w == l => (),
+                    _ => break 'chars,
+                }
+            }
+            if want.len() == 0 {
+                end = Some(start + i + c.len_utf8());
+                break;
+            }
+        }
+
+        if let Some(end) = end {
+            ranges.push((start, end));
+            from = end;
+        }
+    }
+    ranges
+}
+
+/// Renders `line` with every occurrence of `query` highlighted.
+fn highlighted_line<'a>(line: &str, query: &str, size: u16) -> iced::Row<'a, Message> {
+    let mut row = iced::Row::new();
+    let mut last = 0;
+    for (start, end) in match_ranges(line, query) {
+        if start > last {
+            row = row.push(Text::new(&line[last..start]).size(size));
+        }
+        row = row
+            .push(Container::new(Text::new(&line[start..end]).size(size)).style(HighlightStyle {}));
+        last = end;
+    }
+    row.push(Text::new(&line[last..]).size(size))
+}
+
+/// Renders `text` line by line, with every occurrence of `query` highlighted.
+fn highlighted<'a>(text: &str, query: &str, size: u16) -> Element<'a, Message> {
+    let mut lines = Column::new();
+    for line in text.split('\n') {
+        lines = lines.push(highlighted_line(line, query, size));
+    }
+    lines.into()
+}
+
+/// Renders `text` line by line, each after its number. Empty lines are
+/// numbered too, so that the numbers are the same as in an editor.
+fn numbered<'a>(text: &str, query: &str, size: u16) -> Element<'a, Message> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let digits = lines.len().to_string().len();
+    let mut column = Column::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        // Broken up within the line, to keep its number
+        let line = broken_words(line);
+        let line = line.as_ref();
+        let line: Element<Message> = if match_ranges(line, query).is_empty() {
+            // On its own, a line can still wrap at the width that is left
+            Text::new(line).size(size).width(iced::Length::Fill).into()
+        } else {
+            highlighted_line(line, query, size).into()
+        };
+        column = column.push(
+            iced::Row::new()
+                .spacing(10)
+                .push(
+                    Text::new(format!("{:>1$}", i + 1, digits))
+                        .size(size)
+                        .color(MUTED_COLOR),
+                )
+                .push(line),
+        );
+    }
+    column.into()
+}
+
+/// The smallest and largest text sizes that can be set.
+const MIN_TEXT_SIZE: i32 = 8;
+const MAX_TEXT_SIZE: i32 = 72;
+
+fn clamp_text_size(size: i32) -> u16 {
+    size.clamp(MIN_TEXT_SIZE, MAX_TEXT_SIZE) as u16
+}
+
+/// A key, either by where it is on the keyboard or by the character it types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Code(keyboard::KeyCode),
+    Char(char),
+}
+
+/// A keyboard shortcut and the message it sends.
+struct Shortcut {
+    key: Key,
+    command: bool,
+    shift: bool,
+    alt: bool,
+    message: Message,
+    description: &'static str,
+}
+
+const fn with_modifiers(
+    command: bool,
+    shift: bool,
+    key: Key,
+    message: Message,
+    description: &'static str,
+) -> Shortcut {
+    Shortcut {
+        key,
+        command,
+        shift,
+        alt: false,
+        message,
+        description,
+    }
+}
+
+const fn shortcut(key: Key, message: Message, description: &'static str) -> Shortcut {
+    with_modifiers(false, false, key, message, description)
+}
+
+const fn command(key: Key, message: Message, description: &'static str) -> Shortcut {
+    with_modifiers(true, false, key, message, description)
+}
+
+const fn shift(key: Key, message: Message, description: &'static str) -> Shortcut {
+    with_modifiers(false, true, key, message, description)
+}
+
+const fn alt(key: Key, message: Message, description: &'static str) -> Shortcut {
+    Shortcut {
+        key,
+        command: false,
+        shift: false,
+        alt: true,
+        message,
+        description,
+    }
+}
+
+/// Every keyboard shortcut, used both to handle key presses and to list them
+/// in the help. The first one that matches a key press wins, so the ones
+/// needing modifiers come before the same key without them.
+const SHORTCUTS: &[Shortcut] = {
+    use keyboard::KeyCode::*;
+    use Key::{Char, Code};
+    &[
+        command(Code(S), Message::Save, "Save the output"),
+        with_modifiers(
+            true,
+            true,
+            Code(E),
+            Message::ExportFlagged,
+            "Export the flagged entries",
+        ),
+        command(Code(E), Message::ExportCsv, "Export the codes as CSV"),
+        command(
+            Code(R),
+            Message::ExportReport,
+            "Export the progress as a JSON report",
+        ),
+        command(
+            Code(T),
+            Message::ToggleTheme,
+            "Switch between dark and light",
+        ),
+        command(Code(Plus), Message::TextSize(2), "Larger text"),
+        command(Code(Equals), Message::TextSize(2), "Larger text"),
+        command(Code(NumpadAdd), Message::TextSize(2), "Larger text"),
+        command(Code(Minus), Message::TextSize(-2), "Smaller text"),
+        command(Code(NumpadSubtract), Message::TextSize(-2), "Smaller text"),
+        with_modifiers(true, true, Code(Z), Message::Redo, "Redo"),
+        command(Code(Z), Message::Undo, "Undo"),
+        alt(
+            Code(Left),
+            Message::HistoryBack,
+            "Back to where you jumped from",
+        ),
+        alt(
+            Code(Right),
+            Message::HistoryForward,
+            "Forward again after going back",
+        ),
+        shortcut(Code(Right), Message::NextRow, "Next entry"),
+        shortcut(Code(Left), Message::PrevRow, "Previous entry"),
+        shortcut(Code(Home), Message::FirstRow, "First entry"),
+        shortcut(Code(End), Message::LastRow, "Last entry"),
+        shortcut(Code(Space), Message::ToggleMatches, "Toggle matches"),
+        shortcut(
+            Code(Enter),
+            Message::Advance,
+            "Confirm matches and go to the next entry",
+        ),
+        shortcut(
+            Code(S),
+            Message::ShowStats,
+            "Show the code frequencies and the matches by group",
+        ),
+        shortcut(
+            Code(L),
+            Message::ToggleList,
+            "Switch to the list of entries",
+        ),
+        shortcut(Code(G), Message::ScrollToTop, "Scroll back to the top"),
+        shortcut(
+            Code(I),
+            Message::TogglePaths,
+            "Show or hide the input and output files",
+        ),
+        shortcut(Code(F5), Message::Reload, "Read the input file again"),
+        shortcut(Code(K), Message::ToggleCodes, "Show or hide the codes"),
+        shortcut(
+            Code(X),
+            Message::ToggleLongLines,
+            "Show the long lines of a response in full",
+        ),
+        shift(Code(F), Message::NextFlagged, "Next flagged entry"),
+        shortcut(Code(F), Message::ToggleFlag, "Flag the entry"),
+        shift(Code(O), Message::NextNoted, "Next entry with notes"),
+        shortcut(
+            Code(O),
+            Message::ToggleNotesFilter,
+            "Only show the entries with notes",
+        ),
+        alt(
+            Code(D),
+            Message::NextImportDisagreement,
+            "Next entry decided differently in --import-matches",
+        ),
+        shift(
+            Code(D),
+            Message::NextDisagreement,
+            "Next entry coded differently in --compare",
+        ),
+        shortcut(Code(D), Message::ToggleDebug, "Show the entry as JSON"),
+        shortcut(Code(C), Message::CopyResponse, "Copy the response"),
+        shift(Code(P), Message::Unpin, "Stop showing the pinned entry"),
+        shortcut(Code(P), Message::Pin, "Pin the entry, to keep showing it"),
+        shortcut(Code(Y), Message::SetMatch(true), "Set matches"),
+        shortcut(Code(N), Message::SetMatch(false), "Set does not match"),
+        shift(
+            Code(U),
+            Message::NextUnknownCode,
+            "Next entry with codes not in the codebook",
+        ),
+        shortcut(Code(U), Message::ClearMatch, "Set not decided"),
+        shortcut(
+            Code(PageDown),
+            Message::NextGroup,
+            "First entry of the next group",
+        ),
+        shortcut(
+            Code(PageUp),
+            Message::PrevGroup,
+            "First entry of the previous group",
+        ),
+        shift(Code(F3), Message::PrevMatch, "Previous search match"),
+        shortcut(Code(F3), Message::NextMatch, "Next search match"),
+        shortcut(
+            Code(M),
+            Message::CycleMatchesFilter,
+            "Filter by the matches decision",
+        ),
+        shortcut(Code(Escape), Message::ClearFilter, "Clear the filters"),
+        shift(Code(Tab), Message::NextUncoded, "Next entry without codes"),
+        shortcut(Code(Tab), Message::NextUnreviewed, "Next unreviewed entry"),
+        shortcut(Code(Key1), Message::CodeKey(0), "Toggle the numbered code"),
+        shortcut(Code(Key2), Message::CodeKey(1), "Toggle the numbered code"),
+        shortcut(Code(Key3), Message::CodeKey(2), "Toggle the numbered code"),
+        shortcut(Code(Key4), Message::CodeKey(3), "Toggle the numbered code"),
+        shortcut(Code(Key5), Message::CodeKey(4), "Toggle the numbered code"),
+        shortcut(Code(Key6), Message::CodeKey(5), "Toggle the numbered code"),
+        shortcut(Code(Key7), Message::CodeKey(6), "Toggle the numbered code"),
+        shortcut(Code(Key8), Message::CodeKey(7), "Toggle the numbered code"),
+        shortcut(Code(Key9), Message::CodeKey(8), "Toggle the numbered code"),
+        shortcut(Char('?'), Message::ToggleHelp, "Show or hide this help"),
+    ]
+};
+
+impl Shortcut {
+    /// Whether pressing `key` with `modifiers` is this shortcut. Ctrl and
+    /// Alt have to be held exactly when the shortcut needs them, so that
+    /// e.g. Ctrl+Y isn't taken for Y. Shift may be held anyway, for keys
+    /// that need it to be typed, like the plus key.
+    fn matches(&self, key: Key, modifiers: keyboard::Modifiers) -> bool {
+        self.key == key
+            && self.command == modifiers.is_command_pressed()
+            && (!self.shift || modifiers.shift)
+            && self.alt == modifiers.alt
+    }
+
+    fn label(&self) -> String {
+        key_label(self.key, self.command, self.shift, self.alt)
+    }
+}
+
+/// How a key is written in the help and in keymaps, e.g. `Ctrl+Shift+Z`.
+fn key_label(key: Key, command: bool, shift: bool, alt: bool) -> String {
+    let mut label = String::new();
+    if command {
+        label.push_str("Ctrl+");
+    }
+    if shift {
+        label.push_str("Shift+");
+    }
+    if alt {
+        label.push_str("Alt+");
+    }
+    match key {
+        Key::Char(c) => label.push(c),
+        Key::Code(code) => {
+            let name = format!("{:?}", code);
+            // The number keys are called Key1 to Key9
+            label.push_str(name.strip_prefix("Key").unwrap_or(&name));
+        }
+    }
+    label
+}
+
+/// How `message` is named in keymaps, e.g. `NextRow` or `SetMatch(true)`.
+fn message_name(message: &Message) -> String {
+    format!("{:?}", message)
+}
+
+/// Turns a keymap from key labels to message names into one to messages.
+/// The names are those of the messages of the shortcuts, and `Ignore`
+/// to turn a key off.
+fn parse_keymap(names: HashMap<String, String>) -> Result<HashMap<String, Message>, Error> {
+    names
+        .into_iter()
+        .map(|(key, name)| {
+            let message = if name == "Ignore" {
+                Some(Message::Ignore)
+            } else {
+                SHORTCUTS
+                    .iter()
+                    .map(|s| &s.message)
+                    .find(|m| message_name(m) == name)
+                    .cloned()
+            };
+            match message {
+                // Matched against the label of the key that was pressed
+                Some(message) => Ok((key.to_lowercase(), message)),
+                None => Err(format!("Unknown message {} for the key {}", name, key).into()),
+            }
+        })
+        .collect()
+}
+
+/// The message for a key press, if it is a shortcut.
+fn shortcut_message(key: Key, modifiers: keyboard::Modifiers) -> Message {
+    SHORTCUTS
+        .iter()
+        .find(|s| s.matches(key, modifiers))
+        .map_or(Message::Ignore, |s| s.message.clone())
+}
+
+/// Whether typing `c` already does something, through the keymap or a
+/// shortcut.
+fn is_bound(c: char, keymap: &HashMap<String, Message>) -> bool {
+    let shift = c.is_uppercase();
+    let name = c.to_uppercase().to_string();
+    let label = format!("{}{}", if shift { "Shift+" } else { "" }, name);
+    match keymap.get(&label.to_lowercase()) {
+        Some(message) => !matches!(message, Message::Ignore),
+        None => SHORTCUTS.iter().any(|s| {
+            !s.command
+                && (!s.shift || shift)
+                && !s.alt
+                && (s.key == Key::Char(c) || key_label(s.key, false, false, false) == name)
+        }),
+    }
+}
+
+/// The tags of the codes by their hotkeys, leaving out (and reporting) the
+/// ones that are already bound, or taken by an earlier code.
+fn hotkeys_of(codes: &[Code], keymap: &HashMap<String, Message>) -> HashMap<char, String> {
+    let mut hotkeys = HashMap::new();
+    for code in codes {
+        let c = match code.hotkey {
+            Some(c) => c,
+            None => continue,
+        };
+        if is_bound(c, keymap) {
+            eprintln!(
+                "Warning: the hotkey {} of {} is already a shortcut, ignoring it",
+                c, code.tag
+            );
+        } else if let Some(tag) = hotkeys.get(&c) {
+            eprintln!(
+                "Warning: the hotkey {} of {} is already used by {}, ignoring it",
+                c, code.tag, tag
+            );
+        } else {
+            hotkeys.insert(c, code.tag.clone());
+        }
+    }
+    hotkeys
+}
+
+/// The tags on `entry` that are not in `codes`, sorted.
+fn unknown_tags(codes: &[Code], entry: &Entry) -> Vec<String> {
+    let mut tags: Vec<String> = entry
+        .codes
+        .iter()
+        .filter(|tag| !codes.iter().any(|c| c.tag == **tag))
+        .cloned()
+        .collect();
+    tags.sort();
+    tags
+}
+
+/// The number of themes next to each other in a row of the grid.
+const THEMES_PER_ROW: usize = 5;
+
+/// The positions of the themes in each row of the grid, where the last row
+/// may be partial.
+fn theme_rows(count: usize) -> Vec<std::ops::Range<usize>> {
+    (0..count.div_ceil(THEMES_PER_ROW))
+        .map(|row| row * THEMES_PER_ROW..min((row + 1) * THEMES_PER_ROW, count))
+        .collect()
+}
+
+/// The themes of `codes`, in the order they are shown in: by the lowest
+/// `theme_order` of their codes, and then by name.
+fn themes_of(codes: &[Code]) -> Vec<String> {
+    let mut orders: HashMap<&str, Option<i64>> = HashMap::new();
+    for code in codes {
+        let order = orders.entry(code.theme.as_str()).or_insert(None);
+        *order = match (*order, code.theme_order) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    let mut themes: Vec<(Option<i64>, &str)> = orders
+        .into_iter()
+        .map(|(theme, order)| (order, theme))
+        .collect();
+    themes.sort_by_key(|&(order, theme)| (order.is_none(), order, theme));
+    themes
+        .into_iter()
+        .map(|(_, theme)| theme.to_string())
+        .collect()
+}
+
+/// Whether the characters of `query` are all in `text`, in the same order
+/// but not necessarily next to each other, ignoring case.
+fn fuzzy_match(text: &str, query: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| text.any(|t| t == q))
+}
+
+/// Whether at most one code of `theme` can be on an entry.
+fn is_exclusive(codes: &[Code], theme: &str) -> bool {
+    codes
+        .iter()
+        .any(|c| c.theme == theme && c.exclusive == Some(true))
+}
+
+/// Cohen's kappa for the agreement of two raters on the matches
+/// decisions, over the entries both of them decided on. This is NaN if
+/// there are none.
+fn cohens_kappa(a: &[Option<bool>], b: &[Option<bool>]) -> f64 {
+    let pairs: Vec<(bool, bool)> = a
+        .iter()
+        .zip(b)
+        .filter_map(|(a, b)| Some(((*a)?, (*b)?)))
+        .collect();
+    let n = pairs.len() as f64;
+    let agreed = pairs.iter().filter(|(a, b)| a == b).count() as f64 / n;
+
+    // The agreement expected by chance, from how often each rater says yes
+    let a_yes = pairs.iter().filter(|(a, _)| *a).count() as f64 / n;
+    let b_yes = pairs.iter().filter(|(_, b)| *b).count() as f64 / n;
+    let chance = a_yes * b_yes + (1.0 - a_yes) * (1.0 - b_yes);
+
+    if chance == 1.0 {
+        // Both always gave the same answer
+        return 1.0;
+    }
+    (agreed - chance) / (1.0 - chance)
+}
+
+/// How long after going to another entry with the mouse wheel it is
+/// ignored, so that one flick doesn't skip many entries.
+const WHEEL_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// How many entries the list shows at once, around the current one.
+const LIST_LEN: usize = 100;
+
+/// How long after the last change it's saved, so that a burst of changes
+/// is saved once.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// How many edits can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// A single change to an entry, kept so that it can be undone.
+#[derive(Debug, Clone)]
+enum Edit {
+    Matches {
+        idx: usize,
+        old: Option<bool>,
+        new: Option<bool>,
+    },
+    Code {
+        idx: usize,
+        tag: String,
+        added: bool,
+    },
+    Codes {
+        idx: usize,
+        old: HashSet<String>,
+        new: HashSet<String>,
+    },
+    /// Edits to several entries that are undone together, made on `idx`
+    Batch { idx: usize, edits: Vec<Edit> },
+}
+
+impl Edit {
+    fn idx(&self) -> usize {
+        match self {
+            Edit::Matches { idx, .. }
+            | Edit::Code { idx, .. }
+            | Edit::Codes { idx, .. }
+            | Edit::Batch { idx, .. } => *idx,
+        }
+    }
+}
+
+/// The output of another coder, to compare with.
+struct Comparison {
+    path: PathBuf,
+    // By the index of the entry
+    entries: HashMap<u32, Entry>,
+}
+
+struct Viewer {
+    // metadata
+    input_file_path: Box<Path>,
+    output_file_path: Box<Path>,
+    // With --audit-log, where every change is logged
+    audit_log_path: Option<PathBuf>,
+
+    // The actual state
+    idx: usize,
+    autosave: bool,
+    read_only: bool,
+    auto_advance: bool,
+    require_code: bool,
+    // Whether there are changes that have not been saved yet
+    modified: bool,
+    // Whether time was spent on entries since the last save. It's saved
+    // along with the next change, or on closing unless saving is manual,
+    // but isn't a change itself
+    time_spent: bool,
+    // Set by changes that are to be saved once the message is handled
+    save_requested: bool,
+    // Counts the saves that were put off, so that only the last one is done
+    save_generation: u64,
+    // Whether a save is running in the background, and whether there were
+    // changes to save again once it's done
+    saving: bool,
+    save_again: bool,
+    // Set once closing the window waits for a save in the background
+    close_pending: bool,
+    // Set once the window may close
+    exit: bool,
+    // Set once closing the window failed to save
+    close_requested: bool,
+    // Whether closing with unsaved changes asks first, instead of saving
+    confirm_close: bool,
+    // Set while asking
+    show_close_confirm: bool,
+    discard_btn: button::State,
+    // Why the last save or export failed
+    io_error: Option<String>,
+    // What was just done, if it shows nowhere else, until the next entry
+    notice: Option<String>,
+    // Why the output file looks like it's not from this input. Nothing is
+    // saved until this is confirmed
+    overwrite_warning: Option<String>,
+    // When the current entry was navigated to
+    entered_at: Instant,
+    wheel_nav: bool,
+    last_wheel_step: Instant,
+    // From the label of a key, in lowercase, to what it does instead of
+    // its shortcut
+    keymap: HashMap<String, Message>,
+    // The tags of the codes by their hotkeys
+    hotkeys: HashMap<char, String>,
+
+    // The rows
+    data: Vec<Entry>,
+    // The responses that were read from their file, by entry index, or
+    // None if it couldn't be read. Files are read once, when first shown
+    loaded_responses: HashMap<u32, Option<String>>,
+    // With --shuffle, where each entry was in the input
+    original_order: Option<Vec<usize>>,
+    // How the input was read and arranged, to do it again when reloading
+    jsonl: bool,
+    sort_by: Vec<SortKey>,
+    shuffle_seed: Option<u64>,
+    compare: Option<Comparison>,
+    // The decisions from --import-matches by index, to reconcile with
+    imported: HashMap<u32, bool>,
+    codes: Vec<Code>,
+    themes: Vec<String>,
+
+    theme: Theme,
+    text_size: u16,
+    wrap_width: Option<u32>,
+    // Saved in the session, to open the window at it the next time
+    window_size: Option<(u32, u32)>,
+    line_numbers: bool,
+    markdown: bool,
+
+    // The themes whose codes are hidden in the grid
+    collapsed_themes: HashSet<String>,
+    // Only the codes that match this are shown in the grid
+    code_filter: String,
+    code_filter_input: text_input::State,
+
+    // If set, navigation skips the entries without this code
+    filter: Option<String>,
+    filter_list: pick_list::State<String>,
+    // If set, navigation also skips the entries with a different matches
+    // decision, where `Some(None)` is the unreviewed ones
+    matches_filter: Option<Option<bool>>,
+    // If set, navigation also skips the entries without notes
+    notes_filter: bool,
+
+    // The code picked to add to or remove from the whole group
+    group_code: Option<String>,
+    group_list: pick_list::State<String>,
+    group_add_btn: button::State,
+    group_remove_btn: button::State,
+    remove_unknown_btn: button::State,
+
+    // Whether the summary past the last entry is shown
+    show_summary: bool,
+    // Whether the code frequencies are shown, and their scroll position
+    show_stats: bool,
+    stats_scroll: scrollable::State,
+    // Whether many entries are shown at once instead of one, with a button
+    // to open each
+    show_list: bool,
+    list_scroll: scrollable::State,
+    list_btns: Vec<button::State>,
+
+    // Renaming a code, from the overview of the codes
+    rename_from: String,
+    rename_from_input: text_input::State,
+    rename_to: String,
+    rename_to_input: text_input::State,
+    rename_btn: button::State,
+    // A rename into an existing code that was asked for once, and is done
+    // if it's asked for again
+    rename_confirm: Option<(String, String)>,
+    rename_status: String,
+    show_help: bool,
+    help_scroll: scrollable::State,
+    // Whether the files that are read and written are shown at the bottom
+    show_paths: bool,
+    // Whether the codes are shown, or only the response to read
+    show_codes: bool,
+    // Whether the lines longer than `LONG_LINE` are shown in full
+    show_long_lines: bool,
+    // Whether the current entry is shown as JSON next to the response
+    show_debug: bool,
+    debug_scroll: scrollable::State,
+    // The entries jumped between, with the current one at `history_pos`.
+    // Going to the next or previous entry doesn't add to it
+    history: Vec<usize>,
+    history_pos: usize,
+    // An entry shown next to the current one while navigating
+    pinned: Option<usize>,
+    pinned_scroll: scrollable::State,
+
+    // Set if the input or codes could not be loaded, or are unusable, in
+    // which case only the error is shown.
+    load_error: Option<LoadError>,
+
+    // The local state of the two buttons
+    next_btn: button::State,
+    prev_btn: button::State,
+    // Two per theme, to collapse it and to clear its codes
+    theme_btns: Vec<(button::State, button::State)>,
+
+    // The jump-to-index input
+    jump_input: text_input::State,
+    jump_value: String,
+
+    // The search input, and the indices of the entries that match it
+    search_input: text_input::State,
+    search_value: String,
+    search_results: Vec<usize>,
+
+    // The scroll position of the current response
+    response_scroll: scrollable::State,
+
+    // The reviewer's notes on the current entry
+    notes_input: text_input::State,
+
+    // The edits that can be undone, most recent last, and the ones that
+    // were undone and can be redone
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+}
+
+impl Viewer {
+    /// The files that saving writes, along with what goes in them. There
+    /// are none if nothing may be saved.
+    fn save_contents(&self) -> Result<Vec<(PathBuf, Vec<u8>)>, Error> {
+        if self.read_only || self.overwrite_warning.is_some() {
+            return Ok(Vec::new());
+        }
+
+        // Shuffled entries are saved in the order they were loaded in
+        let mut positions: Vec<usize> = (0..self.data.len()).collect();
+        if let Some(order) = &self.original_order {
+            positions.sort_by_key(|&i| order[i]);
+        }
+        let entries: Vec<&Entry> = positions.into_iter().map(|i| &self.data[i]).collect();
+        let session = Session {
+            idx: self.idx,
+            index: Some(self.curr().index),
+            window_size: self.window_size,
+        };
+        Ok(vec![
+            (
+                self.output_file_path.to_path_buf(),
+                io::entries_json(&entries)?,
+            ),
+            (
+                with_suffix(&self.output_file_path, ".session"),
+                io::session_json(&session)?,
+            ),
+        ])
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        for (path, contents) in self.save_contents()? {
+            io::write_file(&path, &contents)?;
+        }
+        Ok(())
+    }
+
+    /// Saves after `SAVE_DELAY`, unless another change comes before then,
+    /// which puts it off again. Closing the window saves what is left.
+    fn save_later(&mut self) -> Command<Message> {
+        self.save_generation += 1;
+        let generation = self.save_generation;
+        Command::perform(
+            async move {
+                // The executor is smol, whose timers need no thread of their own
+                smol::Timer::after(SAVE_DELAY).await;
+                generation
+            },
+            Message::SaveDue,
+        )
+    }
+
+    /// Saves on a background thread, so that a slow disk doesn't hold up
+    /// the window. `Message::Saved` tells how it went. While a save is
+    /// running, another one waits for it to finish, so that the last one
+    /// is always written last.
+    fn save_in_background(&mut self) -> Command<Message> {
+        if self.saving {
+            self.save_again = true;
+            return Command::none();
+        }
+        let files = match self.save_contents() {
+            Ok(files) => files,
+            Err(err) => {
+                self.report(Err(err), "save");
+                return Command::none();
+            }
+        };
+        // Changes from here on are only in the next save
+        self.modified = false;
+        self.time_spent = false;
+        if files.is_empty() {
+            return Command::none();
+        }
+        self.saving = true;
+        Command::perform(
+            async move {
+                for (path, contents) in files {
+                    io::write_file(&path, &contents).map_err(|err| err.to_string())?;
+                }
+                Ok(())
+            },
+            Message::Saved,
+        )
+    }
+
+    /// Closes the window once the changes are saved, or it was decided to
+    /// not save them.
+    fn request_close(&mut self) {
+        // There is nothing to save
+        if self.load_error.is_some() || self.data.is_empty() {
+            self.exit = true;
+            return;
+        }
+        self.track_time(self.idx);
+        if self.saving {
+            // Closing again quits without waiting for it
+            if self.close_pending {
+                self.exit = true;
+            } else {
+                self.close_pending = true;
+                self.save_again = true;
+            }
+            return;
+        }
+        // Without autosave, edits are only saved if asked to
+        if self.confirm_close
+            && self.modified
+            && self.overwrite_warning.is_none()
+            && !self.show_close_confirm
+        {
+            self.show_close_confirm = true;
+            return;
+        }
+        if self.show_close_confirm {
+            // Closing again quits without saving
+            self.exit = true;
+            return;
+        }
+        // Saving by hand, the time spent is kept for the next save instead
+        let unsaved = self.modified || (self.time_spent && !self.confirm_close);
+        if unsaved && !self.save_or_report() && !self.close_requested {
+            // Closing again quits without saving
+            self.close_requested = true;
+            return;
+        }
+        self.exit = true;
+    }
+
+    /// Writes the data as a flat CSV next to the output file.
+    fn export_csv(&mut self) -> Result<(), Error> {
+        self.load_all_responses();
+        let responses: Vec<&str> = self
+            .data
+            .iter()
+            .map(|e| response_text(e, &self.loaded_responses))
+            .collect();
+        io::export_csv(
+            &with_suffix(&self.output_file_path, ".csv"),
+            &self.codes,
+            &self.data,
+            &responses,
+        )
+    }
+
+    /// Writes the flagged entries next to the output file, to be opened in
+    /// the viewer on their own.
+    fn export_flagged(&self) -> Result<(), Error> {
+        let flagged: Vec<&Entry> = self.data.iter().filter(|e| e.flagged).collect();
+        io::export_entries(
+            &with_suffix(&self.output_file_path, ".flagged.json"),
+            &flagged,
+        )
+    }
+
+    /// Writes the progress of the coding next to the output file.
+    fn export_report(&self) -> Result<(), Error> {
+        io::export_report(
+            &with_suffix(&self.output_file_path, ".report.json"),
+            &self.progress_report(),
+        )
+    }
+
+    /// Saves, showing why in the window if that fails. Returns whether it
+    /// saved.
+    fn save_or_report(&mut self) -> bool {
+        let saved = self.save();
+        self.report(saved, "save")
+    }
+
+    /// Shows the error of writing a file, if there is one, in the window.
+    /// Returns whether it was written.
+    fn report(&mut self, written: Result<(), Error>, action: &str) -> bool {
+        match written {
+            Ok(()) => {
+                self.io_error = None;
+                true
+            }
+            Err(err) => {
+                self.io_error = Some(format!("Could not {}: {}", action, err));
+                false
+            }
+        }
+    }
+
+    fn unfocus_inputs(&mut self) {
+        for input in [
+            &mut self.jump_input,
+            &mut self.search_input,
+            &mut self.notes_input,
+            &mut self.code_filter_input,
+            &mut self.rename_from_input,
+            &mut self.rename_to_input,
+        ] {
+            input.unfocus();
+        }
+    }
+
+    /// Marks the data as changed, and saves it right away if autosave is on.
+    fn changed(&mut self) {
+        self.modified = true;
+        if self.autosave {
+            self.save_requested = true;
+        }
+    }
+
+    /// Adds the time since the last navigation to the entry at `idx`, which
+    /// is saved along with the next change.
+    fn track_time(&mut self, idx: usize) {
+        if !self.read_only {
+            self.data[idx].seconds_spent += self.entered_at.elapsed().as_secs_f64();
+            self.time_spent = true;
+        }
+        self.entered_at = Instant::now();
+    }
+
+    /// Moves on to the next unreviewed entry after the current one, if
+    /// there is one before the end.
+    fn advance(&mut self) {
+        if let Some(idx) = self
+            .filtered_indices()
+            .into_iter()
+            .find(|&i| i > self.idx && !self.is_complete(&self.data[i]))
+        {
+            self.idx = idx;
+        }
+    }
+
+    /// Sets the matches decision of the current entry, without saving if it
+    /// already was `matches`.
+    fn set_matches(&mut self, matches: Option<bool>) {
+        let old = self.curr().matches;
+        if old == matches {
+            return;
+        }
+        self.curr_mut().matches = matches;
+        self.record(Edit::Matches {
+            idx: self.idx,
+            old,
+            new: matches,
+        });
+        self.changed();
+    }
+
+    /// Records an edit that was just made, so it can be undone.
+    fn record(&mut self, edit: Edit) {
+        self.audit(&edit, "edit", false);
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+    }
+
+    /// Applies `edit` again, or reverts it if `undo` is set, and moves to
+    /// the entry it was made on.
+    fn apply(&mut self, edit: &Edit, undo: bool) {
+        self.audit(edit, if undo { "undo" } else { "redo" }, undo);
+        self.idx = edit.idx();
+        self.apply_to_data(edit, undo);
+        self.changed();
+    }
+
+    fn apply_to_data(&mut self, edit: &Edit, undo: bool) {
+        match edit {
+            Edit::Matches { idx, old, new } => {
+                self.data[*idx].matches = if undo { *old } else { *new };
+            }
+            Edit::Code { idx, tag, added } => {
+                if *added != undo {
+                    self.data[*idx].codes.insert(tag.clone());
+                } else {
+                    self.data[*idx].codes.remove(tag);
+                }
+            }
+            Edit::Codes { idx, old, new } => {
+                self.data[*idx].codes = if undo { old.clone() } else { new.clone() };
+            }
+            Edit::Batch { edits, .. } => {
+                for edit in edits {
+                    self.apply_to_data(edit, undo);
+                }
+            }
+        }
+    }
+
+    /// Reads the input file again, keeping the coding of the entries that
+    /// are still in it and staying on the current entry if it is. Returns
+    /// how the entries changed.
+    fn reload(&mut self) -> Result<String, Error> {
+        let mut data = load_entries(&self.input_file_path, self.jsonl)?;
+        if data.is_empty() {
+            return Err("It has no entries".into());
+        }
+
+        self.track_time(self.idx);
+        let current = self.curr().index;
+        let pinned = self.pinned.map(|i| self.data[i].index);
+        let mut old: HashMap<u32, Entry> = std::mem::take(&mut self.data)
+            .into_iter()
+            .map(|e| (e.index, e))
+            .collect();
+        let mut added = 0;
+        for entry in &mut data {
+            match old.remove(&entry.index) {
+                Some(saved) => entry.resume(saved),
+                None => added += 1,
+            }
+        }
+        self.original_order = arrange(&mut data, &self.sort_by, self.shuffle_seed);
+        self.data = data;
+
+        // Positions are different now, so everything that holds one is
+        // found again by index, or dropped
+        let entries = &self.data;
+        let position = |index| entries.iter().position(|e| e.index == index);
+        let idx = position(current).unwrap_or(min(self.idx, entries.len() - 1));
+        self.pinned = pinned.and_then(position);
+        self.idx = idx;
+        self.history = vec![self.idx];
+        self.history_pos = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.loaded_responses.clear();
+        self.load_response(self.idx);
+        self.response_scroll = scrollable::State::default();
+        // The coding is the same, but the output has other entries now
+        if !self.read_only && (added > 0 || !old.is_empty()) {
+            self.changed();
+        }
+
+        Ok(format!(
+            "Reloaded {} entries, {} new and {} no longer in the input",
+            self.data.len(),
+            added,
+            old.len()
+        ))
+    }
+
+    /// Adds `edit` to the audit log, if there is one. With `undo`, it's
+    /// logged as going from its new values back to its old ones.
+    fn audit(&mut self, edit: &Edit, action: &'static str, undo: bool) {
+        let path = match &self.audit_log_path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |time| time.as_secs_f64());
+        let mut records = Vec::new();
+        self.audit_records(edit, undo, &mut |index, field, old, new| {
+            records.push(AuditRecord {
+                index,
+                action,
+                field,
+                old,
+                new,
+                timestamp,
+            })
+        });
+        // Writing it shouldn't clear the error of an earlier save
+        if let Err(err) = io::append_audit(&path, &records) {
+            self.report(Err(err), "write the audit log");
+        }
+    }
+
+    /// Passes the index, field, old and new value of every change in `edit`
+    /// to `push`.
+    fn audit_records(
+        &self,
+        edit: &Edit,
+        undo: bool,
+        push: &mut dyn FnMut(u32, String, serde_json::Value, serde_json::Value),
+    ) {
+        let sorted = |tags: &HashSet<String>| {
+            let mut tags: Vec<&String> = tags.iter().collect();
+            tags.sort();
+            serde_json::json!(tags)
+        };
+        let (index, field, old, new) = match edit {
+            Edit::Matches { idx, old, new } => (
+                self.data[*idx].index,
+                String::from("matches"),
+                serde_json::json!(old),
+                serde_json::json!(new),
+            ),
+            Edit::Code { idx, tag, added } => (
+                self.data[*idx].index,
+                format!("code:{}", tag),
+                serde_json::json!(!added),
+                serde_json::json!(added),
+            ),
+            Edit::Codes { idx, old, new } => (
+                self.data[*idx].index,
+                String::from("codes"),
+                sorted(old),
+                sorted(new),
+            ),
+            Edit::Batch { edits, .. } => {
+                for edit in edits {
+                    self.audit_records(edit, undo, push);
+                }
+                return;
+            }
+        };
+        if undo {
+            push(index, field, new, old);
+        } else {
+            push(index, field, old, new);
+        }
+    }
+
+    /// Renames the code `from` to `to`, on every entry and in the codebook
+    /// (but not the codes file). If `to` is a code already, `from` is
+    /// merged into it.
+    fn rename_code(&mut self, from: &str, to: &str) {
+        let mut edits = Vec::new();
+        for (idx, entry) in self.data.iter_mut().enumerate() {
+            if entry.codes.contains(from) {
+                let old = entry.codes.clone();
+                entry.codes.remove(from);
+                entry.codes.insert(to.to_string());
+                edits.push(Edit::Codes {
+                    idx,
+                    old,
+                    new: entry.codes.clone(),
+                });
+            }
+        }
+        if !edits.is_empty() {
+            self.audit(
+                &Edit::Batch {
+                    idx: self.idx,
+                    edits,
+                },
+                "rename",
+                false,
+            );
+        }
+
+        if self.codes.iter().any(|c| c.tag == to) {
+            self.codes.retain(|c| c.tag != from);
+        } else {
+            for code in self.codes.iter_mut().filter(|c| c.tag == from) {
+                code.tag = to.to_string();
+            }
+        }
+        self.themes = themes_of(&self.codes);
+        self.theme_btns
+            .resize(self.themes.len(), Default::default());
+        self.hotkeys = hotkeys_of(&self.codes, &self.keymap);
+
+        for tag in self.filter.iter_mut().chain(self.group_code.iter_mut()) {
+            if tag == from {
+                *tag = to.to_string();
+            }
+        }
+        // The earlier edits are of the old tags
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.changed();
+    }
+
+    /// Adds or removes `tag` on every entry in the group of the current
+    /// one, as a single edit. Adding a code of a single-select theme
+    /// removes the others of the theme.
+    fn set_code_for_group(&mut self, tag: &str, added: bool) {
+        let theme = match self.codes.iter().find(|c| c.tag == tag) {
+            Some(code) => code.theme.clone(),
+            None => return,
+        };
+        let exclusive = added && is_exclusive(&self.codes, &theme);
+
+        let group = self.curr().group.clone();
+        let mut edits = Vec::new();
+        for idx in 0..self.data.len() {
+            if self.data[idx].group != group {
+                continue;
+            }
+            let old = self.data[idx].codes.clone();
+            let mut new: HashSet<String> = old
+                .iter()
+                .filter(|t| {
+                    !exclusive || !self.codes.iter().any(|c| c.tag == **t && c.theme == theme)
+                })
+                .cloned()
+                .collect();
+            if added {
+                new.insert(tag.to_string());
+            } else {
+                new.remove(tag);
+            }
+            if new != old {
+                self.data[idx].codes = new.clone();
+                edits.push(Edit::Codes { idx, old, new });
+            }
+        }
+
+        if !edits.is_empty() {
+            self.record(Edit::Batch {
+                idx: self.idx,
+                edits,
+            });
+            self.changed();
+        }
+    }
+
+    /// Sets the matches decisions of the entries by index, as one edit. The
+    /// entries that already have a different decision keep it, and are
+    /// listed on the terminal.
+    fn import_matches(&mut self, decisions: &[(u32, bool)]) {
+        let positions: HashMap<u32, usize> = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.index, i))
+            .collect();
+        let mut edits = Vec::new();
+        let (mut conflicts, mut missing) = (0, 0);
+        for &(index, matches) in decisions {
+            let idx = match positions.get(&index) {
+                Some(&idx) => idx,
+                None => {
+                    missing += 1;
+                    continue;
+                }
+            };
+            self.imported.insert(index, matches);
+            match self.data[idx].matches {
+                Some(old) if old != matches => {
+                    eprintln!(
+                        "Entry #{} is already decided as matches {}, so it is not set to {}",
+                        index, old, matches
+                    );
+                    conflicts += 1;
+                }
+                Some(_) => (),
+                None => {
+                    self.data[idx].matches = Some(matches);
+                    edits.push(Edit::Matches {
+                        idx,
+                        old: None,
+                        new: Some(matches),
+                    });
+                }
+            }
+        }
+
+        let mut notice = format!("Imported {} matches decisions", edits.len());
+        if conflicts > 0 {
+            notice.push_str(&format!(
+                ", {} differ from the output and were kept as they were",
+                conflicts
+            ));
+        }
+        if missing > 0 {
+            notice.push_str(&format!(", {} are for entries not in the input", missing));
+        }
+        self.notice = Some(notice);
+        if !edits.is_empty() {
+            self.record(Edit::Batch {
+                idx: self.idx,
+                edits,
+            });
+            self.changed();
+        }
+    }
+
+    /// Whether `e` is done: it has a matches decision, and with
+    /// --require-code a code too.
+    fn is_complete(&self, e: &Entry) -> bool {
+        e.matches.is_some() && !(self.require_code && e.codes.is_empty())
+    }
+
+    /// The number of entries that are done.
+    fn reviewed_count(&self) -> usize {
+        self.data.iter().filter(|e| self.is_complete(e)).count()
+    }
+
+    /// The number of entries with the matches decision `matches`.
+    fn matches_count(&self, matches: Option<bool>) -> usize {
+        self.data.iter().filter(|e| e.matches == matches).count()
+    }
+
+    /// How many entries of each group have been reviewed, by group name.
+    fn group_progress(&self) -> Vec<GroupProgress> {
+        let mut groups: BTreeMap<&str, GroupProgress> = BTreeMap::new();
+        for entry in &self.data {
+            let progress = groups
+                .entry(entry.group.as_str())
+                .or_insert_with(|| GroupProgress {
+                    group: entry.group.clone(),
+                    total: 0,
+                    reviewed: 0,
+                });
+            progress.total += 1;
+            if self.is_complete(entry) {
+                progress.reviewed += 1;
+            }
+        }
+        groups.into_values().collect()
+    }
+
+    /// The fraction of the entries of each group that match, by group name.
+    fn matches_rate_by_group(&self) -> Vec<(String, f64)> {
+        let mut groups: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for entry in &self.data {
+            let (total, matching) = groups.entry(entry.group.as_str()).or_default();
+            *total += 1;
+            if entry.matches == Some(true) {
+                *matching += 1;
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(group, (total, matching))| (group.to_string(), matching as f64 / total as f64))
+            .collect()
+    }
+
+    /// The counts that are exported as the report.
+    fn progress_report(&self) -> Report {
+        Report {
+            total: self.data.len(),
+            reviewed: self.reviewed_count(),
+            matches: self.matches_count(Some(true)),
+            does_not_match: self.matches_count(Some(false)),
+            code_frequencies: self
+                .code_frequencies()
+                .into_iter()
+                .map(|(tag, count)| CodeCount { tag, count })
+                .collect(),
+            groups: self.group_progress(),
+        }
+    }
+
+    /// Whether `entry` passes the active filter, if any.
+    fn in_filter(&self, entry: &Entry) -> bool {
+        let has_code = match &self.filter {
+            Some(tag) => entry.codes.contains(tag),
+            None => true,
+        };
+        let has_matches = match self.matches_filter {
+            Some(matches) => entry.matches == matches,
+            None => true,
+        };
+        has_code && has_matches && (!self.notes_filter || !entry.notes.is_empty())
+    }
+
+    /// The indices of the entries that pass the active filters, in order.
+    /// Going to the next or previous entry goes through these.
+    fn filtered_indices(&self) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| self.in_filter(&self.data[i]))
+            .collect()
+    }
+
+    /// Whether `entry` has different codes in the compared file. Entries
+    /// that aren't in it don't count.
+    fn disagrees(&self, entry: &Entry) -> bool {
+        self.compare
+            .as_ref()
+            .and_then(|compare| compare.entries.get(&entry.index))
+            .is_some_and(|other| other.codes != entry.codes)
+    }
+
+    /// The indices of the entries with different codes in the compared file.
+    fn disagreements(&self) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| self.disagrees(&self.data[i]))
+            .collect()
+    }
+
+    /// Whether `entry` has a different matches decision than the imported
+    /// one, including none. Entries that weren't imported don't count.
+    fn differs_from_import(&self, entry: &Entry) -> bool {
+        self.imported
+            .get(&entry.index)
+            .is_some_and(|&imported| entry.matches != Some(imported))
+    }
+
+    /// The indices of the entries with a different decision than imported.
+    fn import_disagreements(&self) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| self.differs_from_import(&self.data[i]))
+            .collect()
+    }
+
+    /// The index of the first entry after the current one that satisfies
+    /// `pred`, wrapping around to the start.
+    fn next_where(&self, pred: impl Fn(&Entry) -> bool) -> Option<usize> {
+        let len = self.data.len();
+        (1..=len)
+            .map(|i| (self.idx + i) % len)
+            .find(|&i| pred(&self.data[i]))
+    }
+
+    /// Reads the responses of all entries that have a file and weren't
+    /// shown yet, for what goes through all of them.
+    fn load_all_responses(&mut self) {
+        for idx in 0..self.data.len() {
+            self.load_response(idx);
+        }
+    }
+
+    /// Reads the response of the entry at `idx` from its file, if it has one
+    /// that wasn't read yet.
+    fn load_response(&mut self, idx: usize) {
+        let entry = &self.data[idx];
+        let path = match &entry.response_path {
+            Some(path) if !self.loaded_responses.contains_key(&entry.index) => path,
+            _ => return,
+        };
+        let path = match self.input_file_path.parent() {
+            Some(dir) => dir.join(path),
+            None => path.clone(),
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => Some(text),
+            Err(err) => {
+                eprintln!("Could not read {}: {}", path.display(), err);
+                None
+            }
+        };
+        self.loaded_responses.insert(entry.index, text);
+    }
+
+    /// The codes in the order they are shown in, theme by theme. The first
+    /// nine can be toggled with the number keys.
+    fn ordered_codes(&self) -> Vec<&Code> {
+        self.themes
+            .iter()
+            .flat_map(|theme| self.codes.iter().filter(move |c| c.theme == *theme))
+            .collect()
+    }
+
+    /// The message that toggles `code` on the current entry, selecting it
+    /// in its theme if the theme is single-select.
+    fn toggle_message(&self, code: &Code) -> Message {
+        let tag = code.tag.clone();
+        let state = !self.curr().codes.contains(&tag);
+        if state && is_exclusive(&self.codes, &code.theme) {
+            Message::CodeSelect(code.theme.clone(), tag)
+        } else {
+            Message::CodeToggle(tag, state)
+        }
+    }
+
+    /// The codes on entries that are not in the codebook, along with the
+    /// index of the entry they are on.
+    fn unknown_code_tags(&self) -> Vec<(usize, String)> {
+        let mut unknown = Vec::new();
+        for (idx, entry) in self.data.iter().enumerate() {
+            let tags = unknown_tags(&self.codes, entry);
+            unknown.extend(tags.into_iter().map(|tag| (idx, tag)));
+        }
+        unknown
+    }
+
+    /// The indices at which a run of entries from the same group starts.
+    fn group_boundaries(&self) -> Vec<usize> {
+        (0..self.data.len())
+            .filter(|&i| i == 0 || self.data[i].group != self.data[i - 1].group)
+            .collect()
+    }
+
+    /// The aggregate matches decisions and codes over all entries.
+    fn view_summary(&mut self) -> Element<'_, Message> {
+        let count = |matches| self.matches_count(matches);
+        let codes_applied: usize = self.data.iter().map(|e| e.codes.len()).sum();
+        let size = self.text_size;
+
+        let content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .push(Text::new(format!("Summary of {} entries", self.data.len())).size(size + 6))
+            .push(Text::new(format!("Matches: {}", count(Some(true)))).size(size))
+            .push(Text::new(format!("Does not match: {}", count(Some(false)))).size(size))
+            .push(Text::new(format!("Not reviewed: {}", count(None))).size(size))
+            .push(Text::new(format!("Codes applied: {}", codes_applied)).size(size))
+            .push(
+                Button::new(&mut self.prev_btn, Text::new("Back").size(size))
+                    .on_press(Message::ExitSummary),
+            );
+
+        Container::new(content)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill)
+            .style(AppStyle { theme: self.theme })
+            .into()
+    }
+
+    /// The entries that pass the filters around the current one, each on
+    /// a line with its matches decision and the start of its response.
+    fn view_list(&mut self) -> Element<'_, Message> {
+        let size = self.text_size;
+        let filtered = self.filtered_indices();
+        let pos = match filtered.binary_search(&self.idx) {
+            Ok(pos) | Err(pos) => pos,
+        };
+        let start = min(
+            pos.saturating_sub(LIST_LEN / 2),
+            filtered.len().saturating_sub(LIST_LEN),
+        );
+        let shown = &filtered[start..min(start + LIST_LEN, filtered.len())];
+        self.list_btns.resize(shown.len(), button::State::default());
+
+        let mut list = Column::new().spacing(5);
+        for (&i, btn) in shown.iter().zip(self.list_btns.iter_mut()) {
+            let entry = &self.data[i];
+            let first_line = response_text(entry, &self.loaded_responses)
+                .lines()
+                .next()
+                .unwrap_or("");
+            let mut label = format!("#{} {}, {}: ", entry.index, entry.lab, entry.group);
+            label.extend(first_line.chars().take(100));
+            list = list.push(
+                iced::Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        Container::new(Space::new(iced::Length::Fill, iced::Length::Fill))
+                            .width(iced::Length::Units(12))
+                            .height(iced::Length::Units(12))
+                            .style(MinimapCell {
+                                matches: entry.matches,
+                                current: i == self.idx,
+                            }),
+                    )
+                    .push(
+                        Button::new(btn, Text::new(label).size(size))
+                            .on_press(Message::OpenEntry(i)),
+                    )
+                    .push(
+                        Text::new(if entry.notes.is_empty() { "" } else { "Notes" })
+                            .size(size.saturating_sub(4))
+                            .color(MUTED_COLOR),
+                    ),
+            );
+        }
+
+        let title = if shown.is_empty() {
+            String::from("No entries pass the filters")
+        } else {
+            format!(
+                "Entries {} to {} of {}",
+                start + 1,
+                start + shown.len(),
+                filtered.len()
+            )
+        };
+        let content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .push(Text::new(title).size(size + 6))
+            .push(
+                Scrollable::new(&mut self.list_scroll)
+                    .height(iced::Length::Fill)
+                    .push(list),
+            );
+
+        Container::new(content)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill)
+            .style(AppStyle { theme: self.theme })
+            .into()
+    }
+
+    /// How many entries each code is applied to, most frequent first.
+    /// Codes that are applied nowhere are included, with a count of zero.
+    fn code_frequencies(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> =
+            self.codes.iter().map(|c| (c.tag.as_str(), 0)).collect();
+        for tag in self.data.iter().flat_map(|e| e.codes.iter()) {
+            *counts.entry(tag.as_str()).or_insert(0) += 1;
+        }
+
+        let mut frequencies: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(tag, count)| (tag.to_string(), count))
+            .collect();
+        frequencies.sort_by(|(a_tag, a), (b_tag, b)| b.cmp(a).then_with(|| a_tag.cmp(b_tag)));
+        frequencies
+    }
+
+    /// How often each code is applied over all entries.
+    fn view_stats(&mut self) -> Element<'_, Message> {
+        let size = self.text_size;
+
+        let mut list = Column::new().spacing(5);
+        for (tag, count) in self.code_frequencies() {
+            list = list.push(
+                iced::Row::new()
+                    .spacing(10)
+                    .push(
+                        Text::new(count.to_string())
+                            .size(size)
+                            .width(iced::Length::Units(60)),
+                    )
+                    .push(Text::new(tag).size(size)),
+            );
+        }
+
+        // Groups with an unusual rate stand out against the others
+        let mut rates = Column::new().spacing(5);
+        for (group, rate) in self.matches_rate_by_group() {
+            rates = rates.push(
+                iced::Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        Text::new(format!("{:.0}%", 100.0 * rate))
+                            .size(size)
+                            .width(iced::Length::Units(60)),
+                    )
+                    .push(
+                        ProgressBar::new(0.0..=1.0, rate as f32)
+                            .width(iced::Length::Units(200))
+                            .height(iced::Length::Units(10)),
+                    )
+                    .push(Text::new(group).size(size)),
+            );
+        }
+
+        let mut content = Column::new().padding(20).spacing(10);
+        if let Some(compare) = &self.compare {
+            let (ours, theirs): (Vec<Option<bool>>, Vec<Option<bool>>) = self
+                .data
+                .iter()
+                .filter_map(|e| Some((e.matches, compare.entries.get(&e.index)?.matches)))
+                .unzip();
+            let both = ours
+                .iter()
+                .zip(&theirs)
+                .filter(|(a, b)| a.is_some() && b.is_some())
+                .count();
+            let agreed = ours
+                .iter()
+                .zip(&theirs)
+                .filter(|(a, b)| a.is_some() && a == b)
+                .count();
+            let reliability = if both == 0 {
+                String::from("No entries were decided on in both files")
+            } else {
+                format!(
+                    "Matches agree on {} of {} entries ({:.1}%), Cohen's kappa {:.3}",
+                    agreed,
+                    both,
+                    100.0 * agreed as f64 / both as f64,
+                    cohens_kappa(&ours, &theirs)
+                )
+            };
+            content = content
+                .push(Text::new("Agreement").size(size + 6))
+                .push(Text::new(reliability).size(size));
+        }
+
+        let rename = iced::Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Rename").size(size))
+            .push(
+                TextInput::new(
+                    &mut self.rename_from_input,
+                    "Tag",
+                    &self.rename_from,
+                    Message::RenameFrom,
+                )
+                .width(iced::Length::Units(200))
+                .padding(5)
+                .size(size),
+            )
+            .push(Text::new("to").size(size))
+            .push(
+                TextInput::new(
+                    &mut self.rename_to_input,
+                    "New tag",
+                    &self.rename_to,
+                    Message::RenameTo,
+                )
+                .on_submit(Message::RenameCode(
+                    self.rename_from.clone(),
+                    self.rename_to.clone(),
+                ))
+                .width(iced::Length::Units(200))
+                .padding(5)
+                .size(size),
+            )
+            .push(
+                Button::new(&mut self.rename_btn, Text::new("Rename").size(size)).on_press(
+                    Message::RenameCode(self.rename_from.clone(), self.rename_to.clone()),
+                ),
+            )
+            .push(Text::new(self.rename_status.as_str()).size(size));
+
+        let content = content
+            .push(Text::new("Code frequencies").size(size + 6))
+            .push(
+                Scrollable::new(&mut self.stats_scroll)
+                    .height(iced::Length::Fill)
+                    .spacing(10)
+                    .push(list)
+                    .push(Text::new("Matches by group").size(size + 6))
+                    .push(rates),
+            )
+            .push(rename)
+            .push(
+                Button::new(&mut self.prev_btn, Text::new("Back").size(size))
+                    .on_press(Message::ShowStats),
+            );
+
+        Container::new(content)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill)
+            .style(AppStyle { theme: self.theme })
+            .into()
+    }
+
+    /// Every keyboard shortcut, with the same description on consecutive
+    /// shortcuts shown on one line, after the ones from the keymap.
+    fn view_help(&mut self) -> Element<'_, Message> {
+        let size = self.text_size;
+
+        let mut custom: Vec<(String, String)> = self
+            .keymap
+            .iter()
+            .map(|(key, message)| (key.clone(), message_name(message)))
+            .collect();
+        custom.sort();
+
+        let mut lines: Vec<(String, &str, String)> = Vec::new();
+        for shortcut in SHORTCUTS {
+            let name = message_name(&shortcut.message);
+            match lines.last_mut() {
+                Some((keys, description, names)) if *description == shortcut.description => {
+                    keys.push_str(", ");
+                    keys.push_str(&shortcut.label());
+                    if !names.ends_with(&name) {
+                        names.push_str(", ");
+                        names.push_str(&name);
+                    }
+                }
+                _ => lines.push((shortcut.label(), shortcut.description, name)),
+            }
+        }
+
+        let line = |keys: String, description: &str, names: String| {
+            iced::Row::new()
+                .spacing(10)
+                .push(
+                    Text::new(keys)
+                        .size(size)
+                        .width(iced::Length::FillPortion(1)),
+                )
+                .push(
+                    Text::new(description)
+                        .size(size)
+                        .width(iced::Length::FillPortion(2)),
+                )
+                .push(
+                    Text::new(names)
+                        .size(size)
+                        .width(iced::Length::FillPortion(1)),
+                )
+        };
+
+        let mut list = Column::new().spacing(5);
+        if !custom.is_empty() {
+            list = list.push(Text::new("From the keymap").size(size + 2));
+            for (key, name) in custom {
+                list = list.push(line(key, "", name));
+            }
+            list = list.push(Text::new("Defaults").size(size + 2));
+        }
+        for (keys, description, names) in lines {
+            list = list.push(line(keys, description, names));
+        }
+
+        let content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .push(Text::new("Keyboard shortcuts").size(size + 6))
+            .push(
+                Text::new(
+                    "Keys can be changed in keys.toml with the names in the last column, \
+                     e.g. j = \"NextRow\"",
+                )
+                .size(size),
+            )
+            .push(
+                Scrollable::new(&mut self.help_scroll)
+                    .height(iced::Length::Fill)
+                    .push(list),
+            )
+            .push(
+                Button::new(&mut self.prev_btn, Text::new("Back").size(size))
+                    .on_press(Message::ToggleHelp),
+            );
+
+        Container::new(content)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill)
+            .style(AppStyle { theme: self.theme })
+            .into()
+    }
+
+    fn curr(&self) -> &Entry {
+        &self.data[self.idx]
+    }
+
+    fn curr_mut(&mut self) -> &mut Entry {
+        &mut self.data[self.idx]
+    }
+
+    /// Handles `message`, which is what `update` does. The clipboard is
+    /// only a trait object here, so that tests can stand in for it.
+    fn handle(
+        &mut self,
+        message: Message,
+        clipboard: &mut dyn iced_native::Clipboard,
+    ) -> Command<Message> {
+        if let Message::Key(key_code, modifiers) = message {
+            let key = Key::Code(key_code);
+            let label = key_label(
+                key,
+                modifiers.is_command_pressed(),
+                modifiers.shift,
+                modifiers.alt,
+            );
+            let message = match self.keymap.get(&label.to_lowercase()) {
+                Some(message) => message.clone(),
+                None => shortcut_message(key, modifiers),
+            };
+            return self.handle(message, clipboard);
+        }
+        if let Message::Char(c) = message {
+            let message = match self.hotkeys.get(&c) {
+                Some(tag) => {
+                    let code = self.codes.iter().find(|code| code.tag == *tag);
+                    code.map_or(Message::Ignore, |code| self.toggle_message(code))
+                }
+                None => shortcut_message(Key::Char(c), keyboard::Modifiers::default()),
+            };
+            return self.handle(message, clipboard);
+        }
+
+        // Only there to redraw the time spent on the entry
+        if let Message::Tick = message {
+            return Command::none();
+        }
+        if let Message::Resized(width, height) = message {
+            self.window_size = Some((width, height));
+            return Command::none();
+        }
+        if let Message::SaveDue(generation) = message {
+            if generation == self.save_generation && self.modified {
+                return self.save_in_background();
+            }
+            return Command::none();
+        }
+        if let Message::Saved(saved) = message {
+            self.saving = false;
+            let saved = self.report(saved.map_err(Error::from), "save");
+            if !saved {
+                self.modified = true;
+            }
+            if self.save_again {
+                self.save_again = false;
+                return self.save_in_background();
+            }
+            if self.close_pending {
+                self.close_pending = false;
+                if saved {
+                    self.exit = true;
+                } else {
+                    self.close_requested = true;
+                }
+            }
+            return Command::none();
+        }
+
+        // Closing the window is left to us, to save first
+        if let Message::CloseRequested = message {
+            self.request_close();
+            return Command::none();
+        }
+
+        // Nothing else can be done before answering, and Esc cancels
+        if self.show_close_confirm {
+            match message {
+                Message::SaveAndClose => {
+                    self.show_close_confirm = false;
+                    if self.save_or_report() {
+                        self.exit = true;
+                    } else {
+                        self.close_requested = true;
+                    }
+                }
+                Message::DiscardAndClose => self.exit = true,
+                Message::CancelClose | Message::ClearFilter => self.show_close_confirm = false,
+                _ => (),
+            }
+            return Command::none();
+        }
+
+        // There is nothing to navigate or code
+        if self.load_error.is_some() || self.data.is_empty() {
+            return Command::none();
+        }
+
+        // The help is dismissed with the key that opened it, or Esc
+        if self.show_help {
+            if let Message::ToggleHelp | Message::ClearFilter = message {
+                self.show_help = false;
+            }
+            return Command::none();
+        }
+
+        // Nothing else can be done before deciding to overwrite the output
+        if self.overwrite_warning.is_some() {
+            if let Message::ConfirmOverwrite = message {
+                self.overwrite_warning = None;
+            }
+            return Command::none();
+        }
+
+        // Neither does the overview of the codes, apart from renaming them
+        if self.show_stats
+            && !matches!(
+                message,
+                Message::RenameFrom(_) | Message::RenameTo(_) | Message::RenameCode(..)
+            )
+        {
+            if let Message::ShowStats = message {
+                self.show_stats = false;
+            }
+            return Command::none();
+        }
+
+        // The summary only responds to going back to the entries
+        if self.show_summary {
+            if let Message::PrevRow | Message::ExitSummary = message {
+                self.show_summary = false;
+            }
+            return Command::none();
+        }
+
+        // The list is for finding entries, the coding is done on them one by one
+        if (self.read_only || self.show_list) && message.is_edit() {
+            return Command::none();
+        }
+
+        let prev_idx = self.idx;
+        let sequential = matches!(
+            message,
+            Message::NextRow
+                | Message::PrevRow
+                | Message::Advance
+                | Message::Matches(_)
+                | Message::SetMatch(_)
+                | Message::HistoryBack
+                | Message::HistoryForward
+        );
+        match message {
+            Message::NextRow => match self.filtered_indices().into_iter().find(|&i| i > self.idx) {
+                Some(idx) => self.idx = idx,
+                None => return self.handle(Message::ShowSummary, clipboard),
+            },
+            Message::ShowSummary => self.show_summary = true,
+            Message::ShowStats => self.show_stats = true,
+            Message::ToggleList => self.show_list = !self.show_list,
+            Message::HistoryBack => {
+                if self.history_pos > 0 {
+                    // Going forward again comes back here
+                    self.history[self.history_pos] = self.idx;
+                    self.history_pos -= 1;
+                    self.idx = self.history[self.history_pos];
+                }
+            }
+            Message::HistoryForward => {
+                if self.history_pos + 1 < self.history.len() {
+                    self.history[self.history_pos] = self.idx;
+                    self.history_pos += 1;
+                    self.idx = self.history[self.history_pos];
+                }
+            }
+            // The positions change, so this skips the handling of moving
+            // to another entry below
+            Message::Reload => {
+                match self.reload() {
+                    Ok(notice) => self.notice = Some(notice),
+                    Err(err) => {
+                        self.report(Err(err), "reload the input");
+                    }
+                }
+                let query = self.search_value.clone();
+                return self.handle(Message::Search(query), clipboard);
+            }
+            Message::TogglePaths => self.show_paths = !self.show_paths,
+            Message::ToggleCodes => self.show_codes = !self.show_codes,
+            Message::ToggleLongLines => self.show_long_lines = !self.show_long_lines,
+            Message::ScrollToTop => {
+                self.response_scroll = scrollable::State::default();
+                self.pinned_scroll = scrollable::State::default();
+                self.debug_scroll = scrollable::State::default();
+                self.list_scroll = scrollable::State::default();
+            }
+            Message::OpenEntry(idx) => {
+                self.idx = idx;
+                self.show_list = false;
+            }
+            Message::ToggleHelp => self.show_help = true,
+            Message::ToggleDebug => self.show_debug = !self.show_debug,
+            Message::Pin => {
+                self.pinned = Some(self.idx);
+                self.pinned_scroll = scrollable::State::default();
+            }
+            Message::Unpin => self.pinned = None,
+            Message::ExitSummary => (),
+            Message::PrevRow => {
+                if let Some(idx) = self
+                    .filtered_indices()
+                    .into_iter()
+                    .rev()
+                    .find(|&i| i < self.idx)
+                {
+                    self.idx = idx;
+                }
+            }
+            Message::FirstRow => {
+                if let Some(&idx) = self.filtered_indices().first() {
+                    self.idx = idx;
+                }
+            }
+            Message::LastRow => {
+                if let Some(&idx) = self.filtered_indices().last() {
+                    self.idx = idx;
+                }
+            }
+            Message::FilterByCode(tag) => self.filter = Some(tag),
+            Message::FilterMatches(matches) => self.matches_filter = Some(matches),
+            Message::CycleMatchesFilter => {
+                let next = match self.matches_filter {
+                    None => Some(false),
+                    Some(Some(false)) => Some(true),
+                    Some(Some(true)) => None,
+                    Some(None) => {
+                        self.matches_filter = None;
+                        return Command::none();
+                    }
+                };
+                return self.handle(Message::FilterMatches(next), clipboard);
+            }
+            Message::ToggleNotesFilter => self.notes_filter = !self.notes_filter,
+            Message::ClearFilter => {
+                self.filter = None;
+                self.matches_filter = None;
+                self.notes_filter = false;
+            }
+            Message::NextUnreviewed => {
+                if let Some(idx) = self.next_where(|e| !self.is_complete(e)) {
+                    self.idx = idx;
+                }
+            }
+            Message::NextUncoded => {
+                if let Some(idx) = self.next_where(|e| e.codes.is_empty()) {
+                    self.idx = idx;
+                }
+            }
+            Message::NextGroup => {
+                let group = &self.curr().group;
+                if let Some(idx) =
+                    (self.idx + 1..self.data.len()).find(|&i| self.data[i].group != *group)
+                {
+                    self.idx = idx;
+                }
+            }
+            Message::PrevGroup => {
+                // Go to the start of the closest run of a different group
+                let group = &self.curr().group;
+                if let Some(prev) = (0..self.idx).rev().find(|&i| self.data[i].group != *group) {
+                    self.idx = self
+                        .group_boundaries()
+                        .into_iter()
+                        .rev()
+                        .find(|&b| b <= prev)
+                        .unwrap_or(prev);
+                }
+            }
+            Message::Search(query) => {
+                if !query.is_empty() {
+                    self.load_all_responses();
+                }
+                self.search_results = if query.is_empty() {
+                    Vec::new()
+                } else {
+                    self.data
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, e)| {
+                            let response = response_text(e, &self.loaded_responses);
+                            !match_ranges(response, &query).is_empty()
+                        })
+                        .map(|(i, _)| i)
+                        .collect()
+                };
+                self.search_value = query;
+            }
+            Message::NextMatch => {
+                if let Some(&idx) = self
+                    .search_results
+                    .iter()
+                    .find(|&&i| i > self.idx)
+                    .or_else(|| self.search_results.first())
+                {
+                    self.idx = idx;
+                }
+            }
+            Message::PrevMatch => {
+                if let Some(&idx) = self
+                    .search_results
+                    .iter()
+                    .rev()
+                    .find(|&&i| i < self.idx)
+                    .or_else(|| self.search_results.last())
+                {
+                    self.idx = idx;
+                }
+            }
+            Message::JumpInput(value) => self.jump_value = value,
+            Message::JumpTo(value) => {
+                // The counter is 1-based, so the input is too
+                if let Ok(idx) = value.trim().parse::<usize>() {
+                    if idx >= 1 && idx <= self.data.len() {
+                        self.idx = idx - 1;
+                    }
+                }
+                self.jump_value.clear();
+                self.unfocus_inputs();
+            }
+            Message::Unfocus => self.unfocus_inputs(),
+            Message::Matches(matches) => {
+                self.set_matches(matches);
+                if self.auto_advance && self.is_complete(self.curr()) {
+                    self.advance();
+                }
+            }
+            Message::SetMatch(matches) => {
+                self.set_matches(Some(matches));
+                // An entry that still needs a code stays open for it
+                if self.is_complete(self.curr()) {
+                    if self.auto_advance {
+                        self.advance();
+                    } else {
+                        return self.handle(Message::NextRow, clipboard);
+                    }
+                }
+            }
+            Message::ClearMatch => self.set_matches(None),
+            Message::Advance => self.advance(),
+            Message::CodeToggle(tag, state) => {
+                let curr = self.curr_mut();
+                let toggled = if state {
+                    curr.codes.insert(tag.clone())
+                } else {
+                    curr.codes.remove(&tag)
+                };
+                if toggled {
+                    self.record(Edit::Code {
+                        idx: self.idx,
+                        tag,
+                        added: state,
+                    });
+                    self.changed();
+                }
+            }
+            Message::CodeSelect(theme, tag) => {
+                let old = self.curr().codes.clone();
+                let mut new: HashSet<String> = old
+                    .iter()
+                    .filter(|t| !self.codes.iter().any(|c| c.tag == **t && c.theme == theme))
+                    .cloned()
+                    .collect();
+                new.insert(tag);
+                if new != old {
+                    self.curr_mut().codes = new.clone();
+                    self.record(Edit::Codes {
+                        idx: self.idx,
+                        old,
+                        new,
+                    });
+                    self.changed();
+                }
+            }
+            Message::ClearTheme(theme) => {
+                let old = self.curr().codes.clone();
+                let new: HashSet<String> = old
+                    .iter()
+                    .filter(|t| !self.codes.iter().any(|c| c.tag == **t && c.theme == theme))
+                    .cloned()
+                    .collect();
+                if new != old {
+                    self.curr_mut().codes = new.clone();
+                    self.record(Edit::Codes {
+                        idx: self.idx,
+                        old,
+                        new,
+                    });
+                    self.changed();
+                }
+            }
+            Message::GroupCodePicked(tag) => self.group_code = Some(tag),
+            Message::ApplyCodeToGroup(tag) => self.set_code_for_group(&tag, true),
+            Message::RemoveCodeFromGroup(tag) => self.set_code_for_group(&tag, false),
+            Message::RenameFrom(value) => self.rename_from = value,
+            Message::RenameTo(value) => self.rename_to = value,
+            Message::RenameCode(from, to) => {
+                let (from, to) = (from.trim().to_string(), to.trim().to_string());
+                let pair = Some((from.clone(), to.clone()));
+                if !self.codes.iter().any(|c| c.tag == from) {
+                    self.rename_status = format!("There is no code {}", from);
+                } else if to.is_empty() || to == from {
+                    self.rename_status = String::from("Enter the new tag");
+                } else if self.codes.iter().any(|c| c.tag == to) && self.rename_confirm != pair {
+                    self.rename_status = format!(
+                        "{} is already a code, rename again to merge {} into it",
+                        to, from
+                    );
+                    self.rename_confirm = pair;
+                } else {
+                    self.rename_code(&from, &to);
+                    self.rename_status = format!("Renamed {} to {}", from, to);
+                    self.rename_confirm = None;
+                    self.rename_from.clear();
+                    self.rename_to.clear();
+                }
+            }
+            Message::CodeKey(n) => {
+                if let Some(code) = self.ordered_codes().get(n) {
+                    let message = self.toggle_message(code);
+                    return self.handle(message, clipboard);
+                }
+            }
+            Message::CodeFilter(query) => self.code_filter = query,
+            Message::ToggleThemeCollapsed(theme) => {
+                if !self.collapsed_themes.remove(&theme) {
+                    self.collapsed_themes.insert(theme);
+                }
+            }
+            Message::ToggleFlag => {
+                self.curr_mut().flagged = !self.curr().flagged;
+                self.changed();
+            }
+            Message::NextDisagreement => {
+                if let Some(idx) = self.next_where(|e| self.disagrees(e)) {
+                    self.idx = idx;
+                }
+            }
+            Message::NextImportDisagreement => {
+                if let Some(idx) = self.next_where(|e| self.differs_from_import(e)) {
+                    self.idx = idx;
+                }
+            }
+            Message::NextUnknownCode => {
+                if let Some(idx) = self.next_where(|e| !unknown_tags(&self.codes, e).is_empty()) {
+                    self.idx = idx;
+                }
+            }
+            Message::RemoveUnknownCodes => {
+                let old = self.curr().codes.clone();
+                let unknown = unknown_tags(&self.codes, self.curr());
+                if !unknown.is_empty() {
+                    let new: HashSet<String> = old
+                        .iter()
+                        .filter(|t| !unknown.contains(t))
+                        .cloned()
+                        .collect();
+                    self.curr_mut().codes = new.clone();
+                    self.record(Edit::Codes {
+                        idx: self.idx,
+                        old,
+                        new,
+                    });
+                    self.changed();
+                }
+            }
+            Message::NextFlagged => {
+                if let Some(idx) = self.next_where(|e| e.flagged) {
+                    self.idx = idx;
+                }
+            }
+            Message::NextNoted => {
+                if let Some(idx) = self.next_where(|e| !e.notes.is_empty()) {
+                    self.idx = idx;
+                }
+            }
+            Message::NotesChanged(notes) => {
+                if self.curr().notes != notes {
+                    self.curr_mut().notes = notes;
+                    self.changed();
+                }
+            }
+            // An entry without a decision counts as not matching
+            Message::ToggleMatches => self.set_matches(Some(!self.curr().matches.unwrap_or(false))),
+            Message::ExportCsv => {
+                let exported = self.export_csv();
+                self.report(exported, "export the CSV");
+            }
+            Message::ExportFlagged => {
+                let exported = self.export_flagged();
+                self.report(exported, "export the flagged entries");
+            }
+            Message::ExportReport => {
+                let exported = self.export_report();
+                self.report(exported, "export the report");
+            }
+            Message::CopyResponse => {
+                clipboard.write(response_text(self.curr(), &self.loaded_responses).to_string());
+                self.notice = Some(String::from("Copied the response"));
+            }
+            Message::ToggleTheme => {
+                self.theme = match self.theme {
+                    Theme::Dark => Theme::Light,
+                    Theme::Light => Theme::Dark,
+                }
+            }
+            Message::TextSize(delta) => {
+                self.text_size = clamp_text_size(self.text_size as i32 + delta as i32)
+            }
+            Message::Undo => {
+                if let Some(edit) = self.undo_stack.pop() {
+                    self.apply(&edit, true);
+                    self.redo_stack.push(edit);
+                }
+            }
+            Message::Redo => {
+                if let Some(edit) = self.redo_stack.pop() {
+                    self.apply(&edit, false);
+                    self.undo_stack.push(edit);
+                }
+            }
+            Message::Save => return self.save_in_background(),
+            Message::Wheel(up) => {
+                if self.wheel_nav && up != 0.0 && self.last_wheel_step.elapsed() >= WHEEL_DEBOUNCE {
+                    self.last_wheel_step = Instant::now();
+                    // Scrolling past the last entry doesn't open the summary
+                    if up > 0.0 {
+                        return self.handle(Message::PrevRow, clipboard);
+                    } else if self.filtered_indices().iter().any(|&i| i > self.idx) {
+                        return self.handle(Message::NextRow, clipboard);
+                    }
+                }
+            }
+            // Handled before anything else
+            Message::CloseRequested
+            | Message::Tick
+            | Message::SaveAndClose
+            | Message::DiscardAndClose
+            | Message::CancelClose
+            | Message::Resized(..)
+            | Message::Saved(_)
+            | Message::SaveDue(_)
+            | Message::Key(..)
+            | Message::Char(_)
+            | Message::ConfirmOverwrite => (),
+            Message::Ignore => (),
+        }
+
+        // A new entry starts scrolled to the top
+        if self.idx != prev_idx && !sequential {
+            // Jumping drops the entries that could be gone forward to
+            self.history.truncate(self.history_pos + 1);
+            self.history[self.history_pos] = prev_idx;
+            self.history.push(self.idx);
+            self.history_pos += 1;
+        }
+        if self.idx != prev_idx {
+            self.load_response(self.idx);
+            self.response_scroll = scrollable::State::default();
+            self.notice = None;
+            self.track_time(prev_idx);
+        }
+        if std::mem::take(&mut self.save_requested) {
+            return self.save_later();
         }
+        Command::none()
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Entry {
-    index: u32,
-    lab: String,
-    group: String,
-    response: String,
-    ratings: Vec<String>,
-    matches: Option<bool>,
-    codes: HashSet<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Code {
-    theme: String,
-    tag: String,
-    code: String,
-}
-
-struct Viewer {
-    // metadata
-    input_file_path: Box<Path>,
-    output_file_path: Box<Path>,
+impl Application for Viewer {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = Args;
 
-    // The actual state
-    idx: usize,
+    fn new(flags: Self::Flags) -> (Self, Command<Message>) {
+        // These are all set by `Args::resolve`
+        let file_path = flags.input.unwrap();
+        let code_paths = flags.codes;
+        let output_file_path = flags.output.unwrap();
+        let loaded = load_entries(&file_path, flags.jsonl)
+            .map_err(|error| LoadError {
+                path: file_path.clone(),
+                error,
+            })
+            .and_then(|data| load_all_codes(&code_paths).map(|codes| (data, codes)));
+        let (mut data, codes, mut load_error) = match loaded {
+            Ok((data, codes)) => (data, codes, None),
+            Err(err) => (Vec::new(), Vec::new(), Some(err)),
+        };
 
-    // The rows
-    data: Vec<Entry>,
-    codes: Vec<Code>,
-    themes: Vec<String>,
+        let sort_by = flags.sort_by;
+        let shuffle_seed = flags.shuffle.map(|seed| {
+            let seed = seed.unwrap_or_else(random_seed);
+            eprintln!("Shuffling the entries with --shuffle={}", seed);
+            seed
+        });
+        let original_order = arrange(&mut data, &sort_by, shuffle_seed);
 
-    // The local state of the two buttons
-    next_btn: button::State,
-    prev_btn: button::State,
-}
+        let compare = flags
+            .compare
+            .and_then(|path| match load_entries(&path, false) {
+                Ok(entries) => Some(Comparison {
+                    entries: entries.into_iter().map(|e| (e.index, e)).collect(),
+                    path,
+                }),
+                Err(error) => {
+                    load_error.get_or_insert(LoadError { path, error });
+                    None
+                }
+            });
 
-impl Viewer {
-    fn save(&self) -> Result<(), Error> {
-        let file = File::create(&self.output_file_path)?;
-        serde_json::to_writer_pretty(file, &self.data)?;
-        Ok(())
-    }
+        let keymap_path = flags
+            .keymap
+            .or_else(|| io::default_keymap_path().filter(|path| path.exists()));
+        let keymap = match keymap_path {
+            Some(path) => match load_keymap(&path).and_then(parse_keymap) {
+                Ok(keymap) => keymap,
+                Err(error) => {
+                    load_error.get_or_insert(LoadError { path, error });
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+        let hotkeys = hotkeys_of(&codes, &keymap);
 
-    fn curr(&self) -> &Entry {
-        &self.data[self.idx]
-    }
+        // Carry on with the coding in the output, unless it has other entries,
+        // in which case it's likely from another dataset
+        let has_output = std::fs::metadata(&output_file_path).is_ok_and(|m| m.len() > 0);
+        let mut mismatch = None;
+        if load_error.is_none() && has_output {
+            match load_entries(&output_file_path, false) {
+                Ok(saved) => {
+                    let others = resume_all(&mut data, saved);
+                    if others > 0 {
+                        mismatch = Some(format!(
+                            "{} has {} entries that are not in {}",
+                            output_file_path.display(),
+                            others,
+                            file_path.display()
+                        ));
+                    }
+                }
+                Err(err) => {
+                    mismatch = Some(format!(
+                        "{} is not a file of entries: {}",
+                        output_file_path.display(),
+                        err
+                    ))
+                }
+            }
+        }
+        let (force, read_only) = (flags.force, flags.read_only);
+        let import_path = flags.import_matches;
+        let overwrite_warning = mismatch.filter(|_| !force && !read_only);
 
-    fn curr_mut(&mut self) -> &mut Entry {
-        &mut self.data[self.idx]
-    }
-}
+        // Keep a copy of the earlier work, in case this session goes wrong.
+        // Without one, the work can still go on, so it's only a warning
+        let mut backup_error = None;
+        if load_error.is_none() && !flags.no_backup && !flags.read_only && output_file_path.exists()
+        {
+            let backup_path = with_suffix(&output_file_path, ".bak");
+            if let Err(error) = std::fs::copy(&output_file_path, &backup_path) {
+                backup_error = Some(format!(
+                    "Could not back up {} to {}: {}",
+                    output_file_path.display(),
+                    backup_path.display(),
+                    error
+                ));
+            }
+        }
 
-impl Application for Viewer {
-    type Executor = executor::Default;
-    type Message = Message;
-    type Flags = ();
+        // Pick up where the last session left off, if we can
+        let idx = load_session(&with_suffix(&output_file_path, ".session"))
+            .map(|session| {
+                session
+                    .index
+                    .and_then(|index| data.iter().position(|e| e.index == index))
+                    .unwrap_or_else(|| min(session.idx, data.len().saturating_sub(1)))
+            })
+            .unwrap_or(0);
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Message>) {
-        let args: Vec<String> = std::env::args().collect();
+        let themes = themes_of(&codes);
 
-        assert_eq!(args.len(), 4);
+        let mut viewer = Self {
+            input_file_path: file_path.into_boxed_path(),
+            audit_log_path: if flags.audit_log {
+                Some(with_suffix(&output_file_path, ".log.jsonl"))
+            } else {
+                None
+            },
+            output_file_path: output_file_path.into_boxed_path(),
+            idx,
+            autosave: !flags.no_autosave && !flags.save_on_exit,
+            read_only: flags.read_only,
+            auto_advance: flags.auto_advance,
+            require_code: flags.require_code,
+            modified: false,
+            time_spent: false,
+            save_requested: false,
+            save_generation: 0,
+            saving: false,
+            save_again: false,
+            close_pending: false,
+            exit: false,
+            close_requested: false,
+            confirm_close: flags.no_autosave && !flags.save_on_exit,
+            show_close_confirm: false,
+            discard_btn: button::State::default(),
+            io_error: backup_error,
+            notice: None,
+            overwrite_warning,
+            entered_at: Instant::now(),
+            wheel_nav: flags.wheel_nav,
+            keymap,
+            hotkeys,
+            last_wheel_step: Instant::now(),
+            data,
+            loaded_responses: HashMap::new(),
+            original_order,
+            jsonl: flags.jsonl,
+            sort_by,
+            shuffle_seed,
+            compare,
+            imported: HashMap::new(),
+            codes,
+            theme_btns: vec![Default::default(); themes.len()],
+            themes,
+            theme: Theme::Dark,
+            text_size: clamp_text_size(flags.text_size as i32),
+            wrap_width: flags.wrap_width,
+            window_size: flags.width.zip(flags.height),
+            line_numbers: flags.line_numbers,
+            markdown: flags.markdown,
+            collapsed_themes: HashSet::new(),
+            code_filter: String::new(),
+            code_filter_input: text_input::State::default(),
+            filter: None,
+            filter_list: pick_list::State::default(),
+            matches_filter: None,
+            notes_filter: false,
+            group_code: None,
+            group_list: pick_list::State::default(),
+            group_add_btn: button::State::default(),
+            group_remove_btn: button::State::default(),
+            remove_unknown_btn: button::State::default(),
+            show_summary: false,
+            show_stats: false,
+            stats_scroll: scrollable::State::default(),
+            show_list: flags.list_mode,
+            list_scroll: scrollable::State::default(),
+            list_btns: Vec::new(),
+            rename_from: String::new(),
+            rename_from_input: text_input::State::default(),
+            rename_to: String::new(),
+            rename_to_input: text_input::State::default(),
+            rename_btn: button::State::default(),
+            rename_confirm: None,
+            rename_status: String::new(),
+            show_help: false,
+            help_scroll: scrollable::State::default(),
+            show_paths: true,
+            show_codes: true,
+            show_long_lines: false,
+            show_debug: false,
+            debug_scroll: scrollable::State::default(),
+            history: vec![idx],
+            history_pos: 0,
+            pinned: None,
+            pinned_scroll: scrollable::State::default(),
+            load_error,
+            next_btn: button::State::default(),
+            prev_btn: button::State::default(),
+            jump_input: text_input::State::default(),
+            jump_value: String::new(),
+            search_input: text_input::State::default(),
+            search_value: String::new(),
+            search_results: Vec::new(),
+            response_scroll: scrollable::State::default(),
+            notes_input: text_input::State::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        };
 
-        let file_path = Path::new(&args[1]);
-        let code_path = Path::new(&args[2]);
-        let output_file_path = Path::new(&args[3]);
-        let file = std::fs::File::open(&file_path).expect(&format!(
-            "Could not open file: {}",
-            file_path.to_str().get_or_insert(&args[1])
-        ));
-        let data: Vec<Entry> = serde_json::from_reader(file).expect("Parsing json...");
-        let file = std::fs::File::open(&code_path).expect(&format!(
-            "Could not open codes file: {}",
-            code_path.to_str().get_or_insert(&args[2])
-        ));
+        // Catch the output drifting from the codebook, e.g. from hand edits
+        for (idx, tag) in viewer.unknown_code_tags() {
+            eprintln!(
+                "Entry #{} has a code that is not in the codebook: {}",
+                viewer.data[idx].index, tag
+            );
+        }
+        // After resuming, so that the decisions in the output come first
+        if let Some(path) = import_path.filter(|_| viewer.load_error.is_none()) {
+            match io::load_matches(&path) {
+                Ok(decisions) => viewer.import_matches(&decisions),
+                Err(error) => viewer.load_error = Some(LoadError { path, error }),
+            }
+        }
+        if !viewer.data.is_empty() {
+            viewer.load_response(viewer.idx);
+        }
 
-        let codes: Vec<Code> = csv::Reader::from_reader(file)
-            .deserialize()
-            .filter_map(|r| r.ok())
-            .collect();
+        (viewer, Command::none())
+    }
 
-        let mut themes: Vec<String> = codes.iter().map(|c| c.theme.clone()).collect();
-        themes.sort();
-        themes.dedup();
-
-        (
-            Self {
-                input_file_path: file_path.into(),
-                output_file_path: output_file_path.into(),
-                idx: 0,
-                data,
-                codes,
-                themes,
-                next_btn: button::State::default(),
-                prev_btn: button::State::default(),
-            },
-            Command::none(),
-        )
+    fn should_exit(&self) -> bool {
+        self.exit
     }
 
     fn title(&self) -> String {
+        if self.load_error.is_some() {
+            return String::from("Response Viewer - Error");
+        }
+        if self.data.is_empty() {
+            return String::from("Response Viewer - No entries");
+        }
+
         let curr = &self.curr();
         format!(
-            "Response Viewer - {}, {}, #{}",
-            curr.lab, curr.group, curr.index
+            "Response Viewer - {}, {}, #{} [{}/{}]{}{}{}",
+            curr.lab,
+            curr.group,
+            curr.index,
+            self.reviewed_count(),
+            self.data.len(),
+            if curr.flagged { " (flagged)" } else { "" },
+            if self.read_only { " (read-only)" } else { "" },
+            if self.modified || self.saving {
+                " *"
+            } else {
+                ""
+            }
         )
     }
 
-    fn update(&mut self, message: Message, _clipboard: &mut Clipboard) -> Command<Self::Message> {
-        match message {
-            Message::NextRow => self.idx = min(self.idx + 1, self.data.len() - 1),
-            Message::PrevRow => self.idx = self.idx.saturating_sub(1),
-            // TODO REALLY need to do better error handling...
-            Message::Matches(matches) => {
-                self.curr_mut().matches = Some(matches);
-                self.save().expect("Saving file");
-            }
-            Message::CodeToggle(tag, state) => {
-                let curr = self.curr_mut();
-                if state {
-                    curr.codes.insert(tag);
-                } else {
-                    curr.codes.remove(&tag);
-                }
-                self.save().expect("Saving file");
-            }
-            Message::ToggleMatches => {
-                self.curr_mut().matches = self.curr_mut().matches.or(Some(false)).map(|b| !b);
-                self.save().expect("Saving file");
-            }
-            Message::Ignore => (),
-        }
-        Command::none()
+    fn update(&mut self, message: Message, clipboard: &mut Clipboard) -> Command<Self::Message> {
+        self.handle(message, clipboard)
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced_native::subscription::events().map(|event| match event {
-            Event::Keyboard(keyboard::Event::KeyPressed {
-                key_code: keyboard::KeyCode::Right,
-                ..
-            }) => Message::NextRow,
+        // A focused text input captures the keys typed into it, and they
+        // don't get here, so they're never taken as shortcuts
+        let events = iced_native::subscription::events().map(|event| match event {
             Event::Keyboard(keyboard::Event::KeyPressed {
-                key_code: keyboard::KeyCode::Left,
-                ..
-            }) => Message::PrevRow,
-            Event::Keyboard(keyboard::Event::KeyPressed {
-                key_code: keyboard::KeyCode::Space,
-                ..
-            }) => Message::ToggleMatches,
+                key_code,
+                modifiers,
+            }) => Message::Key(key_code, modifiers),
+            Event::Keyboard(keyboard::Event::CharacterReceived(c)) => Message::Char(c),
+            Event::Window(window::Event::CloseRequested) => Message::CloseRequested,
+            Event::Window(window::Event::Resized { width, height }) => {
+                Message::Resized(width, height)
+            }
+            // Scrolling over the response scrolls it instead, and doesn't get here
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => match delta {
+                mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } => {
+                    Message::Wheel(y)
+                }
+            },
             _ => Message::Ignore,
-        })
+        });
+        let ticks = iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick);
+        Subscription::batch(vec![events, ticks])
     }
 
-    fn view(&mut self) -> Element<Message> {
+    fn view(&mut self) -> Element<'_, Message> {
+        let size = self.text_size;
+
+        if let Some(err) = &self.load_error {
+            let content = Column::new()
+                .padding(20)
+                .spacing(10)
+                .push(Text::new(format!("Could not load {}", err.path.display())).size(size))
+                .push(
+                    Text::new(err.error.to_string())
+                        .size(size)
+                        .color(ERROR_COLOR),
+                );
+            return Container::new(content)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .style(AppStyle { theme: self.theme })
+                .into();
+        }
+
+        if self.data.is_empty() {
+            let content = Column::new().padding(20).push(
+                Text::new(format!(
+                    "There are no entries in {}",
+                    self.input_file_path.display()
+                ))
+                .size(size),
+            );
+            return Container::new(content)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .style(AppStyle { theme: self.theme })
+                .into();
+        }
+
+        if self.show_close_confirm {
+            let content = Column::new()
+                .padding(20)
+                .spacing(10)
+                .push(Text::new("There are unsaved changes").size(size + 6))
+                .push(
+                    Text::new("Save them before closing, or close without saving them?").size(size),
+                )
+                .push(
+                    iced::Row::new()
+                        .spacing(10)
+                        .push(
+                            Button::new(&mut self.next_btn, Text::new("Save").size(size))
+                                .on_press(Message::SaveAndClose),
+                        )
+                        .push(
+                            Button::new(&mut self.discard_btn, Text::new("Discard").size(size))
+                                .on_press(Message::DiscardAndClose),
+                        )
+                        .push(
+                            Button::new(&mut self.prev_btn, Text::new("Cancel").size(size))
+                                .on_press(Message::CancelClose),
+                        ),
+                );
+            return Container::new(content)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .style(AppStyle { theme: self.theme })
+                .into();
+        }
+
+        if let Some(warning) = &self.overwrite_warning {
+            let content = Column::new()
+                .padding(20)
+                .spacing(10)
+                .push(Text::new(warning.as_str()).size(size).color(ERROR_COLOR))
+                .push(
+                    Text::new(
+                        "Saving will overwrite it. Close the window to keep it, or \
+                         start with --force to skip this question",
+                    )
+                    .size(size),
+                )
+                .push(
+                    Button::new(&mut self.next_btn, Text::new("Overwrite it").size(size))
+                        .on_press(Message::ConfirmOverwrite),
+                );
+            return Container::new(content)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .style(AppStyle { theme: self.theme })
+                .into();
+        }
+
+        if self.show_help {
+            return self.view_help();
+        }
+
+        if self.show_stats {
+            return self.view_stats();
+        }
+
+        if self.show_summary {
+            return self.view_summary();
+        }
+
+        if self.show_list {
+            return self.view_list();
+        }
+
+        let reviewed = self.reviewed_count();
+        let uncoded = self.data.iter().filter(|e| e.codes.is_empty()).count();
+        let disagreements = self.disagreements().len();
+        let import_disagreements = self.import_disagreements().len();
+        let tags: Vec<String> = self.codes.iter().map(|c| c.tag.clone()).collect();
+        let mut filter_status = match &self.filter {
+            Some(tag) => format!("Only showing {}", tag),
+            None => String::from("Filter by code"),
+        };
+        if let Some(matches) = self.matches_filter {
+            filter_status.push_str(match matches {
+                Some(true) => ", matching",
+                Some(false) => ", not matching",
+                None => ", unreviewed",
+            });
+        }
+        if self.notes_filter {
+            filter_status.push_str(", with notes");
+        }
+        // Where the current entry is among the ones the filters let through
+        let filtering = self.filter.is_some() || self.matches_filter.is_some() || self.notes_filter;
+        let position = if filtering {
+            let filtered = self.filtered_indices();
+            match filtered.binary_search(&self.idx) {
+                Ok(pos) => format!("{}/{} (filtered) ", pos + 1, filtered.len()),
+                Err(_) => format!("-/{} (filtered) ", filtered.len()),
+            }
+        } else {
+            String::new()
+        };
+        // The position in all entries comes second while filtering
+        let total_size = if filtering {
+            size.saturating_sub(4)
+        } else {
+            size
+        };
+        let search_status = if self.search_value.is_empty() {
+            String::new()
+        } else {
+            match self.search_results.binary_search(&self.idx) {
+                Ok(pos) => format!("match {} of {}", pos + 1, self.search_results.len()),
+                Err(_) => format!("{} matches", self.search_results.len()),
+            }
+        };
+
         let buttons = iced::Row::new()
             .padding(10)
             .spacing(10)
             .align_items(Align::End)
             .width(iced::Length::Fill)
-            .push(Button::new(&mut self.prev_btn, Text::new("Prev")).on_press(Message::PrevRow))
-            .push(Button::new(&mut self.next_btn, Text::new("Next")).on_press(Message::NextRow));
+            .push(
+                Button::new(&mut self.prev_btn, Text::new("Prev").size(size))
+                    .on_press(Message::PrevRow),
+            )
+            .push(
+                Button::new(&mut self.next_btn, Text::new("Next").size(size))
+                    .on_press(Message::NextRow),
+            );
+
+        let mut io_error = iced::Row::new();
+        if let Some(err) = &self.io_error {
+            let mut message = err.clone();
+            if self.close_requested {
+                message.push_str(", close the window again to quit without saving");
+            }
+            io_error = io_error
+                .padding(10)
+                .push(Text::new(message).size(size).color(ERROR_COLOR));
+        } else if let Some(notice) = &self.notice {
+            io_error = io_error.padding(10).push(Text::new(notice).size(size));
+        }
 
         let footer = iced::Row::new()
-            .height(iced::Length::Fill)
             .width(iced::Length::Fill)
             .align_items(Align::End)
             .push(buttons)
-            .push(iced::Text::new(format!(
-                "{} / {}",
-                self.idx + 1,
-                self.data.len()
-            )));
+            .push(Text::new(position).size(size))
+            .push(
+                TextInput::new(
+                    &mut self.jump_input,
+                    &format!("{}", self.idx + 1),
+                    &self.jump_value,
+                    Message::JumpInput,
+                )
+                .on_submit(Message::JumpTo(self.jump_value.clone()))
+                .width(iced::Length::Units(60))
+                .padding(5)
+                .size(size),
+            )
+            .push(iced::Text::new(format!(" / {}", self.data.len())).size(total_size))
+            .push(
+                iced::Row::new()
+                    .padding(10)
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        ProgressBar::new(0.0..=self.data.len() as f32, reviewed as f32)
+                            .width(iced::Length::Units(200))
+                            .height(iced::Length::Units(10)),
+                    )
+                    .push(Text::new(format!("{} reviewed", reviewed)).size(size))
+                    .push(
+                        Text::new(format!("{} without codes", uncoded))
+                            .size(size)
+                            .color(MUTED_COLOR),
+                    ),
+            )
+            .push(
+                iced::Row::new()
+                    .padding(10)
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        TextInput::new(
+                            &mut self.search_input,
+                            "Search",
+                            &self.search_value,
+                            Message::Search,
+                        )
+                        .on_submit(Message::NextMatch)
+                        .width(iced::Length::Units(200))
+                        .padding(5)
+                        .size(size),
+                    )
+                    .push(Text::new(search_status).size(size)),
+            )
+            .push(
+                iced::Row::new()
+                    .padding(10)
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(Text::new(filter_status).size(size))
+                    .push(
+                        PickList::new(
+                            &mut self.filter_list,
+                            tags.clone(),
+                            self.filter.clone(),
+                            Message::FilterByCode,
+                        )
+                        .text_size(size),
+                    ),
+            );
 
-        let title = iced::Row::new().padding(10).spacing(10).push(Text::new({
-            let row = &self.data[self.idx];
-            format!("{}, {}, {}", row.lab, row.group, row.index)
-        }));
+        let mut title = iced::Row::new().padding(10).spacing(10).push(
+            Text::new({
+                let row = &self.data[self.idx];
+                format!("{}, {}, {}", row.lab, row.group, row.index)
+            })
+            .size(size),
+        );
+        if self.data[self.idx].flagged {
+            title = title.push(Text::new("Flagged").size(size).color(FLAG_COLOR));
+        }
+        if self.require_code
+            && self.data[self.idx].matches.is_some()
+            && self.data[self.idx].codes.is_empty()
+        {
+            title = title.push(Text::new("Needs a code").size(size).color(ERROR_COLOR));
+        }
+        // Redrawn every second by the ticks
+        let seconds =
+            (self.data[self.idx].seconds_spent + self.entered_at.elapsed().as_secs_f64()) as u64;
+        title = title.push(
+            Text::new(format!("{}:{:02}", seconds / 60, seconds % 60)).size(size.saturating_sub(4)),
+        );
 
         let mut ratings = iced::Column::new().padding(10);
         for rating in &self.data[self.idx].ratings {
-            ratings = ratings.push(Text::new(rating));
+            ratings = ratings.push(Text::new(rating.to_string()).size(size));
+        }
+        if self.data[self.idx].ratings.is_empty() {
+            ratings = ratings.push(Text::new("no ratings").size(size).color(MUTED_COLOR));
         }
 
-        let text = iced::Row::new()
+        let response = response_text(&self.data[self.idx], &self.loaded_responses);
+        let (response, cut) = if self.show_long_lines {
+            (Cow::Borrowed(response), false)
+        } else {
+            shortened(response)
+        };
+        let searched = !match_ranges(&response, &self.search_value).is_empty();
+        let response: Element<Message> = if self.line_numbers {
+            numbered(&response, &self.search_value, size)
+        } else if self.markdown && !searched {
+            // The matches of a search are shown in the text as it's written
+            markdown::render(&broken_words(&response), size, self.theme)
+        } else {
+            let response = broken_words(&response);
+            if !searched {
+                Text::new(response).size(size).into()
+            } else {
+                highlighted(&response, &self.search_value, size)
+            }
+        };
+        let mut response = Container::new(response);
+        if let Some(width) = self.wrap_width {
+            response = response.max_width(width);
+        }
+        let mut response_col = Scrollable::new(&mut self.response_scroll)
             .padding(10)
-            .push(Text::new(&self.data[self.idx].response));
+            .spacing(10)
+            .width(iced::Length::FillPortion(2))
+            .height(iced::Length::Fill);
+        if let Some(context) = &self.data[self.idx].context {
+            response_col = response_col.push(
+                Container::new(Text::new(context.as_str()).size(size))
+                    .padding(10)
+                    .width(iced::Length::Fill)
+                    .style(ContextStyle { theme: self.theme }),
+            );
+        }
+        if cut {
+            response_col = response_col.push(
+                Text::new("Some lines are cut short, press X to show them in full")
+                    .size(size)
+                    .color(FLAG_COLOR),
+            );
+        }
+        let mut text = iced::Row::new()
+            .height(iced::Length::Fill)
+            .push(response_col.push(response));
+        if let Some(pinned) = self.pinned {
+            let entry = &self.data[pinned];
+            text = text.push(
+                Scrollable::new(&mut self.pinned_scroll)
+                    .padding(10)
+                    .spacing(10)
+                    .width(iced::Length::FillPortion(2))
+                    .height(iced::Length::Fill)
+                    .push(
+                        Text::new(format!(
+                            "Pinned: {}, {}, #{}",
+                            entry.lab, entry.group, entry.index
+                        ))
+                        .size(size)
+                        .color(FLAG_COLOR),
+                    )
+                    .push({
+                        let response = response_text(entry, &self.loaded_responses);
+                        let (response, _) = if self.show_long_lines {
+                            (Cow::Borrowed(response), false)
+                        } else {
+                            shortened(response)
+                        };
+                        Text::new(broken_words(&response)).size(size)
+                    }),
+            );
+        }
+        if self.show_debug {
+            // Exactly what would be saved, including the fields not shown
+            let json = serde_json::to_string_pretty(&self.data[self.idx])
+                .unwrap_or_else(|err| err.to_string());
+            text = text.push(
+                Scrollable::new(&mut self.debug_scroll)
+                    .padding(10)
+                    .width(iced::Length::FillPortion(1))
+                    .height(iced::Length::Fill)
+                    .push(Text::new(json).size(size.saturating_sub(4))),
+            );
+        }
 
-        let input = iced::Row::new().padding(10).push(Checkbox::new(
-            *self.data[self.idx].matches.get_or_insert(false),
-            "Matches",
-            Message::Matches,
-        ));
+        // Not deciding is shown apart from deciding that it doesn't match
+        let mut input = iced::Row::new().padding(10).spacing(20);
+        for (value, label) in [
+            (Some(true), "Matches"),
+            (Some(false), "Does not match"),
+            (None, "Not decided"),
+        ] {
+            let mut radio = Radio::new(
+                value,
+                label,
+                Some(self.data[self.idx].matches),
+                Message::Matches,
+            )
+            .text_size(size);
+            if self.read_only {
+                radio = radio.style(Dimmed {});
+            }
+            input = input.push(radio);
+        }
+        let applied = self.data[self.idx].codes.len();
+        input = input.push(
+            Text::new(format!(
+                "{} {} applied",
+                applied,
+                if applied == 1 { "code" } else { "codes" }
+            ))
+            .size(size),
+        );
+
+        let all_codes = &self.codes;
+        let query = self.code_filter.trim();
+        let shown = |code: &Code| {
+            query.is_empty() || fuzzy_match(&code.code, query) || fuzzy_match(&code.tag, query)
+        };
+        // The themes without a code that matches the search are left out
+        let themes: Vec<&String> = self
+            .themes
+            .iter()
+            .filter(|theme| all_codes.iter().any(|c| c.theme == **theme && shown(c)))
+            .collect();
+        // How many codes come before each theme, in the order of
+        // `ordered_codes`, so that the numbers stay the same when searching
+        let mut numbers_before: HashMap<&str, usize> = HashMap::new();
+        let mut count = 0;
+        for theme in &self.themes {
+            numbers_before.insert(theme, count);
+            count += all_codes.iter().filter(|c| c.theme == *theme).count();
+        }
 
-        let mut codes = iced::Column::new();
-        for row_idx in 0..self.themes.len() / 5 {
+        let mut codes = iced::Column::new().push(
+            iced::Row::new().padding(10).push(
+                TextInput::new(
+                    &mut self.code_filter_input,
+                    "Search codes",
+                    &self.code_filter,
+                    Message::CodeFilter,
+                )
+                .on_submit(Message::Unfocus)
+                .width(iced::Length::Units(300))
+                .padding(5)
+                .size(size),
+            ),
+        );
+        let mut theme_btns = self.theme_btns.iter_mut();
+        for range in theme_rows(themes.len()) {
             let mut row = iced::Row::new();
-            let start_idx = row_idx * 5;
-            let end_idx = min((row_idx + 1) * 5, self.themes.len());
-            for theme in self.themes[start_idx..end_idx].iter() {
-                let mut theme_col = iced::Column::new().push(Text::new(theme)).padding(10);
-                for code in self.codes.iter().filter(|c| c.theme == *theme) {
+            for (&theme, (btn, clear_btn)) in themes[range].iter().zip(&mut theme_btns) {
+                let mut code_number = numbers_before[theme.as_str()];
+                let collapsed = self.collapsed_themes.contains(theme);
+                let header = format!("[{}] {}", if collapsed { "+" } else { "-" }, theme);
+                let mut clear = Button::new(clear_btn, Text::new("Clear").size(size));
+                let on_entry = &self.data[self.idx].codes;
+                let applied = all_codes
+                    .iter()
+                    .any(|c| c.theme == *theme && on_entry.contains(&c.tag));
+                if applied && !self.read_only {
+                    clear = clear.on_press(Message::ClearTheme(theme.clone()));
+                }
+                let mut theme_col = iced::Column::new()
+                    .push(
+                        iced::Row::new()
+                            .spacing(5)
+                            .push(
+                                Button::new(btn, Text::new(header).size(size))
+                                    .on_press(Message::ToggleThemeCollapsed(theme.clone())),
+                            )
+                            .push(clear),
+                    )
+                    .padding(10);
+                if collapsed {
+                    row = row.push(theme_col);
+                    continue;
+                }
+                let exclusive = is_exclusive(all_codes, theme);
+                // Wide enough for a number and a hotkey
+                let hint_width = if self.hotkeys.is_empty() {
+                    size / 2 + 1
+                } else {
+                    size + 2
+                };
+                for (code_idx, code) in all_codes.iter().enumerate() {
+                    if code.theme != *theme {
+                        continue;
+                    }
+                    code_number += 1;
+                    if !shown(code) {
+                        continue;
+                    }
                     let tag: String = code.tag.to_string();
                     let toggle: bool = self.data[self.idx].codes.contains(&tag);
-                    let checkbox = Checkbox::new(toggle, &code.code.clone(), move |b| {
-                        Message::CodeToggle(tag.clone(), b)
-                    });
-                    theme_col = theme_col.push(checkbox);
+                    let checkbox: Element<Message> = if exclusive {
+                        // The radio buttons of a theme are told apart by
+                        // their index in the codebook
+                        let theme = theme.clone();
+                        let mut radio = Radio::new(
+                            code_idx,
+                            &code.code,
+                            if toggle { Some(code_idx) } else { None },
+                            move |_| Message::CodeSelect(theme.clone(), tag.clone()),
+                        )
+                        .text_size(size);
+                        if self.read_only {
+                            radio = radio.style(Dimmed {});
+                        }
+                        radio.into()
+                    } else {
+                        let mut checkbox = Checkbox::new(toggle, &code.code.clone(), move |b| {
+                            Message::CodeToggle(tag.clone(), b)
+                        })
+                        .text_size(size);
+                        if self.read_only {
+                            checkbox = checkbox.style(Dimmed {});
+                        }
+                        checkbox.into()
+                    };
+                    let checkbox = match code.description.as_deref() {
+                        Some(description) if !description.trim().is_empty() => {
+                            Tooltip::new(checkbox, description, tooltip::Position::FollowCursor)
+                                .size(size)
+                                .padding(10)
+                                .style(ContextStyle { theme: self.theme })
+                                .into()
+                        }
+                        _ => checkbox,
+                    };
+                    let mut key_hint = if code_number <= 9 {
+                        code_number.to_string()
+                    } else {
+                        String::new()
+                    };
+                    if let Some(c) = code.hotkey {
+                        if self.hotkeys.get(&c) == Some(&code.tag) {
+                            if !key_hint.is_empty() {
+                                key_hint.push(' ');
+                            }
+                            key_hint.push(c);
+                        }
+                    }
+                    theme_col = theme_col.push(
+                        iced::Row::new()
+                            .spacing(5)
+                            .align_items(Align::Center)
+                            .push(
+                                Text::new(key_hint)
+                                    .size(size.saturating_sub(4))
+                                    .width(iced::Length::Units(hint_width)),
+                            )
+                            .push(checkbox),
+                    );
                 }
                 row = row.push(theme_col);
             }
             codes = codes.push(row);
         }
 
-        let content = Column::new()
+        // Codes that the grid can't show, e.g. from hand edits
+        let mut unknown_codes = iced::Row::new()
+            .padding(10)
+            .spacing(10)
+            .align_items(Align::Center);
+        let unknown = unknown_tags(&self.codes, &self.data[self.idx]);
+        if !unknown.is_empty() {
+            let mut remove = Button::new(
+                &mut self.remove_unknown_btn,
+                Text::new("Remove them").size(size),
+            );
+            if !self.read_only {
+                remove = remove.on_press(Message::RemoveUnknownCodes);
+            }
+            unknown_codes = unknown_codes
+                .push(
+                    Text::new(format!("Not in the codebook: {}", unknown.join(", ")))
+                        .size(size)
+                        .color(ERROR_COLOR),
+                )
+                .push(remove);
+        }
+
+        // The codes of both coders side by side, marking the ones only one
+        // of them used
+        let mut comparison = iced::Row::new().padding(10).spacing(40);
+        if let Some(compare) = &self.compare {
+            let own = &self.data[self.idx].codes;
+            let other = compare.entries.get(&self.data[self.idx].index);
+            let name = compare
+                .path
+                .file_name()
+                .unwrap_or(compare.path.as_os_str())
+                .to_string_lossy();
+
+            let tag_list = |title: String, tags: &HashSet<String>, others: &HashSet<String>| {
+                let mut tags: Vec<&String> = tags.iter().collect();
+                tags.sort();
+                let mut list = Column::new().spacing(5).push(Text::new(title).size(size));
+                for tag in tags {
+                    let mut text = Text::new(tag.as_str()).size(size);
+                    if !others.contains(tag) {
+                        text = text.color(ERROR_COLOR);
+                    }
+                    list = list.push(text);
+                }
+                list
+            };
+
+            comparison =
+                comparison.push(Text::new(format!("{} disagreements", disagreements)).size(size));
+            match other {
+                Some(other) => {
+                    comparison = comparison
+                        .push(tag_list(String::from("Here"), own, &other.codes))
+                        .push(tag_list(format!("In {}", name), &other.codes, own));
+                }
+                None => {
+                    comparison = comparison.push(Text::new(format!("Not in {}", name)).size(size));
+                }
+            }
+        }
+
+        if let Some(&imported) = self.imported.get(&self.data[self.idx].index) {
+            let name = |matches: Option<bool>| match matches {
+                Some(true) => "Matches",
+                Some(false) => "Does not match",
+                None => "Not decided",
+            };
+            let here = self.data[self.idx].matches;
+            let mut imported_text =
+                Text::new(format!("Imported: {}", name(Some(imported)))).size(size);
+            if here != Some(imported) {
+                imported_text = imported_text.color(ERROR_COLOR);
+            }
+            comparison = comparison
+                .push(
+                    Text::new(format!("{} differ from the import", import_disagreements))
+                        .size(size),
+                )
+                .push(Text::new(format!("Here: {}", name(here))).size(size))
+                .push(imported_text);
+        }
+
+        // Behind a pick list, so that a whole group isn't changed by accident
+        let mut add_to_group = Button::new(&mut self.group_add_btn, Text::new("Add").size(size));
+        let mut remove_from_group =
+            Button::new(&mut self.group_remove_btn, Text::new("Remove").size(size));
+        if let Some(tag) = &self.group_code {
+            add_to_group = add_to_group.on_press(Message::ApplyCodeToGroup(tag.clone()));
+            remove_from_group =
+                remove_from_group.on_press(Message::RemoveCodeFromGroup(tag.clone()));
+        }
+        let group_codes = iced::Row::new()
+            .padding(10)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new(format!("Whole group {}:", self.data[self.idx].group)).size(size))
+            .push(
+                PickList::new(
+                    &mut self.group_list,
+                    tags,
+                    self.group_code.clone(),
+                    Message::GroupCodePicked,
+                )
+                .text_size(size),
+            )
+            .push(add_to_group)
+            .push(remove_from_group);
+
+        let notes = iced::Row::new().padding(10).push(
+            TextInput::new(
+                &mut self.notes_input,
+                "Notes",
+                &self.data[self.idx].notes,
+                Message::NotesChanged,
+            )
+            .on_submit(Message::Unfocus)
+            .padding(5)
+            .size(size),
+        );
+
+        let mut content = Column::new()
             .padding(20)
             .push(title)
             .push(ratings)
-            .push(input)
-            .push(codes)
+            .push(input);
+        if self.show_codes {
+            content = content.push(codes);
+        }
+        let mut content = content
+            .push(unknown_codes)
+            .push(comparison)
+            .push(group_codes)
+            .push(notes)
             .push(text)
+            .push(minimap(&self.data, self.idx))
+            .push(io_error)
             .push(footer);
+        if self.show_paths {
+            content = content.push(
+                Text::new(format!(
+                    "{} -> {}",
+                    self.input_file_path.display(),
+                    self.output_file_path.display()
+                ))
+                .size(size.saturating_sub(4))
+                .color(MUTED_COLOR),
+            );
+        }
 
-        let container = Container::new(content).style(AppStyle {});
+        let container = Container::new(content).style(AppStyle { theme: self.theme });
 
         container.into()
     }
 }
 
 fn main() -> iced::Result {
+    let args = Args::parse().resolve();
+    let default_size = iced::window::Settings::default().size;
     Viewer::run(Settings {
+        window: iced::window::Settings {
+            size: (
+                args.width.unwrap_or(default_size.0),
+                args.height.unwrap_or(default_size.1),
+            ),
+            ..iced::window::Settings::default()
+        },
         antialiasing: true,
-        default_text_size: 18,
-        ..Settings::default()
+        default_text_size: clamp_text_size(args.text_size as i32),
+        exit_on_close_request: false,
+        ..Settings::with_flags(args)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A viewer on the JSON `entries` with the codebook `codes`, as CSV. Its files are in a directory of
+    /// their own, named after `name`.
+    fn viewer(name: &str, entries: &str, codes: &str, args: &[&str]) -> Viewer {
+        let dir =
+            std::env::temp_dir().join(format!("response_viewer_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [
+            dir.join("input.json"),
+            dir.join("codes.csv"),
+            dir.join("output.json"),
+        ];
+        std::fs::write(&paths[0], entries).unwrap();
+        std::fs::write(&paths[1], codes).unwrap();
+
+        let mut argv: Vec<&str> = vec!["response_reader", "--no-backup"];
+        argv.extend(args);
+        argv.extend(paths.iter().map(|path| path.to_str().unwrap()));
+        let (viewer, _) = Viewer::new(Args::parse_from(argv).resolve());
+        assert!(viewer.load_error.is_none());
+        viewer
+    }
+
+    #[test]
+    fn codes_not_in_the_codebook_are_found() {
+        let viewer = viewer(
+            "unknown_codes",
+            r#"[
+                {"index": 1, "lab": "a", "group": "g", "response": "r", "codes": ["known"]},
+                {"index": 2, "lab": "a", "group": "g", "response": "r", "codes": ["known", "gone"]}
+            ]"#,
+            "theme,tag,code\nt,known,A known code\n",
+            &[],
+        );
+        assert_eq!(viewer.unknown_code_tags(), vec![(1, String::from("gone"))]);
+    }
+
+    #[test]
+    fn a_partial_row_of_themes_is_kept() {
+        assert_eq!(theme_rows(7), vec![0..5, 5..7]);
+        assert_eq!(theme_rows(5), vec![0..5]);
+        assert!(theme_rows(0).is_empty());
+    }
+
+    #[test]
+    fn plain_shortcuts_need_no_ctrl_or_alt() {
+        // Ctrl, or Cmd on macOS
+        let ctrl = keyboard::Modifiers {
+            control: true,
+            logo: true,
+            ..keyboard::Modifiers::default()
+        };
+        let alt = keyboard::Modifiers {
+            alt: true,
+            ..keyboard::Modifiers::default()
+        };
+        let y = Key::Code(keyboard::KeyCode::Y);
+        assert!(matches!(
+            shortcut_message(y, keyboard::Modifiers::default()),
+            Message::SetMatch(true)
+        ));
+        assert!(matches!(shortcut_message(y, ctrl), Message::Ignore));
+        assert!(matches!(shortcut_message(y, alt), Message::Ignore));
+        assert!(matches!(
+            shortcut_message(Key::Code(keyboard::KeyCode::Z), ctrl),
+            Message::Undo
+        ));
+    }
+
+    struct NoClipboard;
+
+    impl iced_native::Clipboard for NoClipboard {
+        fn read(&self) -> Option<String> {
+            None
+        }
+
+        fn write(&mut self, _contents: String) {}
+    }
+
+    /// Handles `message` like the window would, along with the messages
+    /// that the commands it gives back end in, such as the delayed saves.
+    fn send(viewer: &mut Viewer, message: Message) {
+        let mut messages = vec![message];
+        while let Some(message) = messages.pop() {
+            for future in viewer.handle(message, &mut NoClipboard).futures() {
+                messages.push(smol::block_on(future));
+            }
+        }
+    }
+
+    #[test]
+    fn setting_the_same_decision_changes_nothing() {
+        let mut viewer = viewer(
+            "same_decision",
+            r#"[
+                {"index": 1, "lab": "a", "group": "g", "response": "r", "matches": true},
+                {"index": 2, "lab": "a", "group": "g", "response": "r", "matches": true}
+            ]"#,
+            "theme,tag,code\nt,c,A code\n",
+            &[],
+        );
+        viewer.save().unwrap();
+        let path = viewer.output_file_path.to_path_buf();
+        let written = || {
+            let contents = std::fs::read_to_string(&path).unwrap();
+            (
+                contents,
+                std::fs::metadata(&path).unwrap().modified().unwrap(),
+            )
+        };
+        let before = written();
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Setting it to what it is moves on, but doesn't save
+        send(&mut viewer, Message::Matches(Some(true)));
+        send(&mut viewer, Message::SetMatch(true));
+        assert_eq!(viewer.idx, 1);
+        assert!(!viewer.modified);
+        assert!(viewer.undo_stack.is_empty());
+        assert_eq!(written(), before);
+
+        send(&mut viewer, Message::ToggleMatches);
+        assert_ne!(written().0, before.0);
+    }
+
+    fn entry(index: u32, lab: &str, group: &str) -> Entry {
+        serde_json::from_value(serde_json::json!({
+            "index": index,
+            "lab": lab,
+            "group": group,
+            "response": "r",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sorting_keeps_the_order_of_equal_keys() {
+        let mut data = vec![
+            entry(1, "b", "x"),
+            entry(2, "a", "y"),
+            entry(3, "b", "w"),
+            entry(4, "a", "z"),
+        ];
+        assert_eq!(arrange(&mut data, &[SortKey::Lab], None), None);
+        let indices: Vec<u32> = data.iter().map(|e| e.index).collect();
+        assert_eq!(indices, vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn kappa_of_a_known_table() {
+        // 20 both yes, 5 only the first, 10 only the second, 15 both no
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        for (count, first, second) in [
+            (20, true, true),
+            (5, true, false),
+            (10, false, true),
+            (15, false, false),
+        ] {
+            a.extend(std::iter::repeat_n(Some(first), count));
+            b.extend(std::iter::repeat_n(Some(second), count));
+        }
+        assert!((cohens_kappa(&a, &b) - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn kappa_of_full_agreement_is_one() {
+        let a = [Some(true), Some(false), None, Some(true)];
+        assert_eq!(cohens_kappa(&a, &a), 1.0);
+        // Even when both only ever said yes
+        assert_eq!(cohens_kappa(&[Some(true); 3], &[Some(true); 3]), 1.0);
+    }
+
+    #[test]
+    fn kappa_without_shared_decisions_is_nan() {
+        assert!(cohens_kappa(&[], &[]).is_nan());
+        assert!(cohens_kappa(&[Some(true), None], &[None, Some(false)]).is_nan());
+    }
+
+    #[test]
+    fn resuming_goes_by_index() {
+        let mut data = vec![entry(1, "a", "g"), entry(2, "a", "g"), entry(3, "a", "g")];
+        let mut first = entry(3, "other", "other");
+        first.matches = Some(true);
+        first.notes = String::from("noted");
+        let mut second = entry(1, "a", "g");
+        second.codes.insert(String::from("c"));
+
+        assert_eq!(resume_all(&mut data, vec![first, second]), 0);
+        assert!(data[0].codes.contains("c"));
+        assert_eq!(data[1].matches, None);
+        assert_eq!(data[2].matches, Some(true));
+        assert_eq!(data[2].notes, "noted");
+        // The response and what describes it stay as in the input
+        assert_eq!(data[2].lab, "a");
+    }
+
+    #[test]
+    fn resuming_from_other_entries_takes_over_nothing() {
+        let mut data = vec![entry(1, "a", "g")];
+        let mut saved = entry(1, "a", "g");
+        saved.matches = Some(false);
+        assert_eq!(resume_all(&mut data, vec![saved, entry(9, "a", "g")]), 1);
+        assert_eq!(data[0].matches, None);
+    }
+
+    #[test]
+    fn a_renamed_code_keeps_its_hotkey() {
+        let mut viewer = viewer(
+            "renamed_hotkey",
+            r#"[{"index": 1, "lab": "a", "group": "g", "response": "r"}]"#,
+            "theme,tag,code,hotkey\nt,old,A code,q\n",
+            &[],
+        );
+        viewer.rename_code("old", "new");
+        assert_eq!(viewer.hotkeys.get(&'q'), Some(&String::from("new")));
+    }
+
+    #[test]
+    fn responses_in_files_that_werent_shown_are_exported() {
+        let mut viewer = viewer(
+            "response_files",
+            r#"[
+                {"index": 1, "lab": "a", "group": "g", "response": "shown"},
+                {"index": 2, "lab": "a", "group": "g", "response": "", "response_path": "two.txt"}
+            ]"#,
+            "theme,tag,code\nt,c,A code\n",
+            &[],
+        );
+        let dir = viewer.output_file_path.parent().unwrap().to_path_buf();
+        std::fs::write(dir.join("two.txt"), "from the file").unwrap();
+
+        viewer.export_csv().unwrap();
+        let csv = std::fs::read_to_string(dir.join("output.json.csv")).unwrap();
+        assert!(csv.contains("from the file"));
+    }
+
+    #[test]
+    fn closing_without_entries_just_closes() {
+        let mut viewer = viewer("no_entries", "[]", "theme,tag,code\nt,c,A code\n", &[]);
+        viewer.request_close();
+        assert!(viewer.exit);
+    }
+
+    #[test]
+    fn renaming_a_code_is_logged() {
+        let mut viewer = viewer(
+            "renamed_log",
+            r#"[
+                {"index": 1, "lab": "a", "group": "g", "response": "r", "codes": ["old"]},
+                {"index": 2, "lab": "a", "group": "g", "response": "r"}
+            ]"#,
+            "theme,tag,code\nt,old,A code\n",
+            &["--audit-log"],
+        );
+        viewer.rename_code("old", "new");
+        let log = std::fs::read_to_string(viewer.audit_log_path.as_ref().unwrap()).unwrap();
+        let records: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["index"], 1);
+        assert_eq!(records[0]["action"], "rename");
+        assert_eq!(records[0]["old"], serde_json::json!(["old"]));
+        assert_eq!(records[0]["new"], serde_json::json!(["new"]));
+    }
+
+    #[test]
+    fn matches_rate_by_group_name() {
+        let viewer = viewer(
+            "matches_rate",
+            r#"[
+                {"index": 1, "lab": "a", "group": "y", "response": "r", "matches": true},
+                {"index": 2, "lab": "a", "group": "x", "response": "r", "matches": true},
+                {"index": 3, "lab": "a", "group": "y", "response": "r"},
+                {"index": 4, "lab": "a", "group": "x", "response": "r", "matches": true},
+                {"index": 5, "lab": "a", "group": "y", "response": "r", "matches": true},
+                {"index": 6, "lab": "a", "group": "y", "response": "r", "matches": false}
+            ]"#,
+            "theme,tag,code\nt,c,A code\n",
+            &[],
+        );
+        assert_eq!(
+            viewer.matches_rate_by_group(),
+            vec![(String::from("x"), 1.0), (String::from("y"), 0.5)]
+        );
+    }
+
+    #[test]
+    fn closing_without_autosave_only_asks_after_edits() {
+        let entries = r#"[{"index": 1, "lab": "a", "group": "g", "response": "r"}]"#;
+        let codes = "theme,tag,code\nt,c,A code\n";
+
+        // Only time was spent on the entry, which isn't written on its own
+        let mut unedited = viewer("close_unedited", entries, codes, &["--no-autosave"]);
+        unedited.track_time(0);
+        unedited.request_close();
+        assert!(!unedited.show_close_confirm);
+        assert!(unedited.exit);
+        assert!(!unedited.output_file_path.exists());
+
+        let mut edited = viewer("close_edited", entries, codes, &["--no-autosave"]);
+        edited.set_matches(Some(true));
+        edited.request_close();
+        assert!(edited.show_close_confirm);
+        assert!(!edited.exit);
+    }
+
+    #[test]
+    fn reloading_the_same_entries_changes_nothing() {
+        let entries = r#"[{"index": 1, "lab": "a", "group": "g", "response": "r"}]"#;
+        let mut viewer = viewer("reload", entries, "theme,tag,code\n", &["--no-autosave"]);
+        viewer.reload().unwrap();
+        assert!(!viewer.modified);
+
+        let entries = r#"[
+            {"index": 1, "lab": "a", "group": "g", "response": "r"},
+            {"index": 2, "lab": "a", "group": "g", "response": "r"}
+        ]"#;
+        std::fs::write(&viewer.input_file_path, entries).unwrap();
+        viewer.reload().unwrap();
+        assert!(viewer.modified);
+    }
+
+    #[test]
+    fn cycling_the_match_filter_goes_through_every_decision() {
+        let entries = r#"[{"index": 1, "lab": "a", "group": "g", "response": "r"}]"#;
+        let mut viewer = viewer("match_filter", entries, "theme,tag,code\n", &[]);
+        for filter in [Some(Some(false)), Some(Some(true)), Some(None), None] {
+            send(&mut viewer, Message::CycleMatchesFilter);
+            assert_eq!(viewer.matches_filter, filter);
+        }
+    }
+
+    #[test]
+    fn going_past_the_last_entry_shows_the_summary() {
+        let entries = r#"[{"index": 1, "lab": "a", "group": "g", "response": "r"}]"#;
+        let mut viewer = viewer("summary", entries, "theme,tag,code\n", &[]);
+        send(&mut viewer, Message::NextRow);
+        assert!(viewer.show_summary);
+        assert_eq!(viewer.idx, 0);
+    }
+}
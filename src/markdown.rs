@@ -0,0 +1,180 @@
+//! Rendering responses that are written in Markdown.
+
+use crate::{ContextStyle, Message, Theme, MUTED_COLOR};
+use iced::{Column, Container, Element, Space, Text};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag};
+
+const EMPHASIS_COLOR: iced::Color = iced::Color {
+    r: 0x6C as f32 / 255.0,
+    g: 0x71 as f32 / 255.0,
+    b: 0xC4 as f32 / 255.0,
+    a: 1.0,
+};
+
+const STRONG_COLOR: iced::Color = iced::Color {
+    r: 0x26 as f32 / 255.0,
+    g: 0x8B as f32 / 255.0,
+    b: 0xD2 as f32 / 255.0,
+    a: 1.0,
+};
+
+/// How far a list or quote is indented, in pixels per level.
+const INDENT: u16 = 20;
+
+/// A piece of a line, and how it's set off from the rest.
+struct Span {
+    text: String,
+    color: Option<iced::Color>,
+}
+
+/// Builds the blocks of a response one after the other, from the spans
+/// that make up each.
+struct Blocks<'a> {
+    column: Column<'a, Message>,
+    spans: Vec<Span>,
+    indent: u16,
+}
+
+impl<'a> Blocks<'a> {
+    fn push_text(&mut self, text: &str, color: Option<iced::Color>) {
+        self.spans.push(Span {
+            text: text.to_string(),
+            color,
+        });
+    }
+
+    /// Adds the spans since the last block as a line of text.
+    fn flush(&mut self, size: u16) {
+        if self.spans.is_empty() {
+            return;
+        }
+        let mut row = iced::Row::new().push(Space::with_width(iced::Length::Units(self.indent)));
+        if let [span] = self.spans.as_slice() {
+            // On its own, the text can still wrap at the width that is left
+            let mut text = Text::new(span.text.as_str())
+                .size(size)
+                .width(iced::Length::Fill);
+            if let Some(color) = span.color {
+                text = text.color(color);
+            }
+            row = row.push(text);
+        } else {
+            for span in &self.spans {
+                let mut text = Text::new(span.text.as_str()).size(size);
+                if let Some(color) = span.color {
+                    text = text.color(color);
+                }
+                row = row.push(text);
+            }
+        }
+        self.spans.clear();
+        self.column = std::mem::replace(&mut self.column, Column::new()).push(row);
+    }
+
+    fn push(&mut self, element: impl Into<Element<'a, Message>>) {
+        let row = iced::Row::new()
+            .push(Space::with_width(iced::Length::Units(self.indent)))
+            .push(element);
+        self.column = std::mem::replace(&mut self.column, Column::new()).push(row);
+    }
+}
+
+/// Renders `text` as Markdown: headings are larger, lists are indented and
+/// numbered or bulleted, and emphasis, strong text and code are colored.
+/// The text of a line is only wrapped if it has no emphasis in it, as the
+/// parts of a line can't wrap together.
+pub(crate) fn render<'a>(text: &str, size: u16, theme: Theme) -> Element<'a, Message> {
+    let mut blocks = Blocks {
+        column: Column::new().spacing(size / 2),
+        spans: Vec::new(),
+        indent: 0,
+    };
+    // The number of the next item of each list that's open, if it's ordered
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut emphasis = 0;
+    let mut strong = 0;
+    let mut code_block: Option<String> = None;
+
+    for event in Parser::new(text) {
+        let color = if strong > 0 {
+            Some(STRONG_COLOR)
+        } else if emphasis > 0 {
+            Some(EMPHASIS_COLOR)
+        } else {
+            None
+        };
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => code_block = Some(String::new()),
+            Event::End(Tag::CodeBlock(_)) => {
+                let code = code_block.take().unwrap_or_default();
+                blocks.push(
+                    Container::new(Text::new(code.trim_end()).size(size))
+                        .padding(10)
+                        .width(iced::Length::Fill)
+                        .style(ContextStyle { theme }),
+                );
+            }
+            Event::Text(text) => match &mut code_block {
+                Some(code) => code.push_str(&text),
+                None => blocks.push_text(&text, color),
+            },
+            Event::Code(code) => blocks.push_text(&code, Some(MUTED_COLOR)),
+            Event::Html(html) => blocks.push_text(&html, color),
+            Event::Start(Tag::Emphasis) => emphasis += 1,
+            Event::End(Tag::Emphasis) => emphasis -= 1,
+            Event::Start(Tag::Strong) => strong += 1,
+            Event::End(Tag::Strong) => strong -= 1,
+            Event::End(Tag::Heading(level, ..)) => {
+                let larger = match level {
+                    HeadingLevel::H1 => 8,
+                    HeadingLevel::H2 => 6,
+                    HeadingLevel::H3 => 4,
+                    _ => 2,
+                };
+                blocks.flush(size + larger);
+            }
+            Event::End(Tag::Paragraph) | Event::HardBreak => blocks.flush(size),
+            Event::SoftBreak => blocks.push_text(" ", None),
+            Event::Start(Tag::BlockQuote) => {
+                blocks.flush(size);
+                blocks.indent += INDENT;
+            }
+            Event::End(Tag::BlockQuote) => {
+                blocks.flush(size);
+                blocks.indent -= INDENT;
+            }
+            Event::Start(Tag::List(first)) => {
+                blocks.flush(size);
+                if !lists.is_empty() {
+                    blocks.indent += INDENT;
+                }
+                lists.push(first);
+            }
+            Event::End(Tag::List(_)) => {
+                blocks.flush(size);
+                lists.pop();
+                if !lists.is_empty() {
+                    blocks.indent -= INDENT;
+                }
+            }
+            Event::Start(Tag::Item) => {
+                let marker = match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => String::from("• "),
+                };
+                blocks.push_text(&marker, None);
+            }
+            Event::End(Tag::Item) => blocks.flush(size),
+            Event::Rule => {
+                blocks.flush(size);
+                blocks.push(Text::new("———").size(size).color(MUTED_COLOR));
+            }
+            _ => (),
+        }
+    }
+    blocks.flush(size);
+    blocks.column.into()
+}
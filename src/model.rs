@@ -0,0 +1,164 @@
+//! The data that is coded, and the codebook it is coded against.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::PathBuf};
+
+/// A rating of a response, either on its own or along with who gave it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Rating {
+    Plain(String),
+    Rated { rater: String, value: String },
+}
+
+impl std::fmt::Display for Rating {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rating::Plain(value) => write!(f, "{}", value),
+            Rating::Rated { rater, value } => write!(f, "{}: {}", rater, value),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub index: u32,
+    pub lab: String,
+    pub group: String,
+    pub response: String,
+    // A file to read the response from instead, relative to the input file.
+    // If it can't be read, `response` is shown
+    #[serde(default)]
+    pub response_path: Option<PathBuf>,
+    // What the response is answering, e.g. the prompt
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub ratings: Vec<Rating>,
+    #[serde(default)]
+    pub matches: Option<bool>,
+    #[serde(default)]
+    pub codes: HashSet<String>,
+    #[serde(default)]
+    pub notes: String,
+    // Set aside to come back to, separately from the matches decision
+    #[serde(default)]
+    pub flagged: bool,
+    // How long the entry was looked at, over all visits
+    #[serde(default)]
+    pub seconds_spent: f64,
+}
+
+impl Entry {
+    /// Takes over the coding of `saved`, an earlier copy of this entry,
+    /// while keeping the response and what describes it from this one.
+    pub fn resume(&mut self, saved: Entry) {
+        self.matches = saved.matches;
+        self.codes = saved.codes;
+        self.notes = saved.notes;
+        self.flagged = saved.flagged;
+        self.seconds_spent = saved.seconds_spent;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Code {
+    pub theme: String,
+    pub tag: String,
+    pub code: String,
+    /// From the optional `exclusive` column. A theme is single-select if
+    /// any of its codes is marked `true`.
+    #[serde(default)]
+    pub exclusive: Option<bool>,
+    /// From the optional `hotkey` column, a character that toggles the code.
+    #[serde(default)]
+    pub hotkey: Option<char>,
+    /// From the optional `theme_order` column. Themes with a lower number
+    /// are shown first, and the ones without come last.
+    #[serde(default)]
+    pub theme_order: Option<i64>,
+    /// From the optional `description` column, the full coding rule, which
+    /// is shown when hovering over the code.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// The progress of the coding, written for use outside the viewer.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub total: usize,
+    pub reviewed: usize,
+    pub matches: usize,
+    pub does_not_match: usize,
+    /// Most frequent first, as in the code frequencies screen.
+    pub code_frequencies: Vec<CodeCount>,
+    /// By the name of the group.
+    pub groups: Vec<GroupProgress>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupProgress {
+    pub group: String,
+    pub total: usize,
+    pub reviewed: usize,
+}
+
+/// A line of the audit log, for one change to one field of an entry.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    pub index: u32,
+    /// Whether the change was made, undone or redone, or is from renaming
+    /// a code.
+    pub action: &'static str,
+    /// `matches`, `codes`, or `code:<tag>` for a single code.
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+    /// In seconds since the Unix epoch.
+    pub timestamp: f64,
+}
+
+/// Viewer state that is kept between runs, next to the output file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    // The position of the entry that was open, which is only used for
+    // sessions from before `index` was saved
+    #[serde(default)]
+    pub idx: usize,
+    // The index of the entry that was open, so that it's found again even
+    // if the entries are in a different order
+    #[serde(default)]
+    pub index: Option<u32>,
+    // The width and height the window was left at
+    #[serde(default)]
+    pub window_size: Option<(u32, u32)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_entry_without_coding_gets_the_defaults() {
+        let entry: Entry =
+            serde_json::from_str(r#"{"index":1,"lab":"a","group":"b","response":"c"}"#).unwrap();
+        assert_eq!(entry.index, 1);
+        assert_eq!(entry.lab, "a");
+        assert_eq!(entry.group, "b");
+        assert_eq!(entry.response, "c");
+        assert_eq!(entry.response_path, None);
+        assert_eq!(entry.context, None);
+        assert!(entry.ratings.is_empty());
+        assert_eq!(entry.matches, None);
+        assert!(entry.codes.is_empty());
+        assert_eq!(entry.notes, "");
+        assert!(!entry.flagged);
+        assert_eq!(entry.seconds_spent, 0.0);
+    }
+}